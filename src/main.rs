@@ -15,7 +15,7 @@ use tui::backend::TermionBackend;
 use tui::layout::{Constraint, Direction, Layout};
 use tui::Terminal;
 
-use parser::{stringify, Project};
+use parser::{stringify, LicenseStatus, Project};
 
 #[allow(dead_code)]
 fn printer(config: &Project) {
@@ -23,8 +23,8 @@ fn printer(config: &Project) {
         for name in config.get_deps_in_group(&group.value) {
             let name = &name;
             let specified_version = &config.get_specified_version(&group.value, name);
-            let current_version = &config.get_current_version(name);
-            let latest_version = &config.get_latest_version(name);
+            let current_version = &config.get_current_version(&group.value, name);
+            let latest_version = &config.get_latest_version(&group.value, name);
             let latest_semver_version = &config.get_semver_version(&group.value, name);
             println!(
                 "[{}] {} : {}({}) => {}({})",
@@ -39,12 +39,87 @@ fn printer(config: &Project) {
     }
 }
 
+/// Print every way a package ends up in the project, for `depman why`
+fn why(project: &Project, name: &str) -> Result<(), Box<dyn Error>> {
+    let paths = project.get_dependency_paths(name);
+    if paths.is_empty() {
+        return Err(format!("{} is not in the dependency graph", name).into());
+    }
+    for path in paths {
+        println!("{}", path);
+    }
+    Ok(())
+}
+
+/// Print the dependencies whose licenses go against the license policy,
+/// failing if there are any, for `depman licenses` in CI
+fn licenses(project: &Project) -> Result<(), Box<dyn Error>> {
+    if let Some(e) = project.get_license_policy_error() {
+        return Err(e.into());
+    }
+    if !project.has_license_policy() {
+        return Err("No license policy in .depman.toml".into());
+    }
+    let violations = project.get_licenses_with_status(LicenseStatus::Denied);
+    for (group, name, license) in &violations {
+        println!(
+            "[{}] {} {}: {}",
+            group,
+            name,
+            stringify(&project.get_current_version(group, name)),
+            license.as_deref().unwrap_or("unknown license")
+        );
+    }
+    // Free text licenses have to be checked by hand
+    for (group, name, license) in project.get_licenses_with_status(LicenseStatus::Unknown) {
+        println!(
+            "[{}] {} {}: {} (unknown)",
+            group,
+            name,
+            stringify(&project.get_current_version(&group, &name)),
+            license.unwrap_or_default()
+        );
+    }
+    // Not being able to fetch a license must not look like a pass
+    let unchecked = project.get_unchecked_licenses();
+    for (group, name) in &unchecked {
+        println!("[{}] {}: could not fetch the license", group, name);
+    }
+    if !violations.is_empty() {
+        Err(format!(
+            "{} dependencies go against the license policy",
+            violations.len()
+        )
+        .into())
+    } else if !unchecked.is_empty() {
+        Err(format!(
+            "The licenses of {} dependencies could not be checked",
+            unchecked.len()
+        )
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
+    // depman why <package> [folder]
+    if args.len() > 2 && args[1] == "why" {
+        let folder = if args.len() > 3 { &args[3] } else { "." };
+        let kind = Project::determine_kind(folder).expect("Unsupported package manager");
+        return why(&Project::parse_local(folder, &kind), &args[2]);
+    }
+    // depman licenses [folder]
+    if args.len() > 1 && args[1] == "licenses" {
+        let folder = if args.len() > 2 { &args[2] } else { "." };
+        let kind = Project::determine_kind(folder).expect("Unsupported package manager");
+        return licenses(&Project::parse(folder, &kind).await);
+    }
     let folder = if args.len() > 1 { &args[1] } else { "." };
     let kind = Project::determine_kind(&folder).expect("Unsupported package manager");
-    println!("Fetching dependency info...");
+    println!("Fetching {} dependency info...", kind.name());
     let project = Project::parse(folder, &kind).await;
     // printer(&project);
 
@@ -64,6 +139,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
         app.next();
 
         let mut search_in_next_iter: Option<String> = None;
+        let mut install_in_next_iter = false;
         let mut reload = false;
 
         loop {
@@ -91,6 +167,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 app.render_tabs(&mut f, tabl);
                 app.render_dependency_info(&mut f, chunks[1]);
                 app.render_version_selector(&mut f);
+                app.render_features_selector(&mut f);
+                app.render_dependency_tree(&mut f);
+                app.render_why_list(&mut f);
                 app.render_help_menu(&mut f);
                 app.display_message(&mut f);
                 app.display_search_input(&mut f);
@@ -110,6 +189,24 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 continue;
             }
 
+            if install_in_next_iter {
+                install_in_next_iter = false;
+                match app.install_checked_dep().await {
+                    Ok(message) => {
+                        app.set_message(&message);
+                        reload = true;
+                    }
+                    Err(e) => app.set_message(&e),
+                }
+                continue;
+            }
+
+            if let PopupKind::Tree = app.popup {
+                if app.fetch_tree_metadata().await {
+                    continue;
+                }
+            }
+
             if let Event::Input(input) = events.next()? {
                 match app.popup {
                     PopupKind::SearchInput => match input {
@@ -141,6 +238,63 @@ async fn main() -> Result<(), Box<dyn Error>> {
                         }
                         _ => {}
                     },
+                    PopupKind::Features => match input {
+                        Key::Char(' ') => app.toggle_feature(),
+                        Key::Char('\n') => {
+                            if app.save_features() {
+                                app.set_message("Features updated!");
+                            } else {
+                                app.set_message("Updating features failed.");
+                            }
+                            reload = true;
+                        }
+                        Key::Char('f') | Key::Esc => app.toggle_features_menu(),
+                        Key::Down | Key::Char('j') => app.next(),
+                        Key::Up | Key::Char('k') => app.previous(),
+                        Key::Char('g') => app.top(),
+                        Key::Char('G') => app.bottom(),
+                        Key::Char('q') => break,
+                        _ => {}
+                    },
+                    PopupKind::Tree => match input {
+                        Key::Char(' ') | Key::Char('\n') => app.toggle_tree_node(),
+                        Key::Char('t') | Key::Esc => app.toggle_tree_menu(),
+                        Key::Char('w') => app.toggle_why_menu(),
+                        Key::Down | Key::Char('j') => app.next(),
+                        Key::Up | Key::Char('k') => app.previous(),
+                        Key::Char('g') => app.top(),
+                        Key::Char('G') => app.bottom(),
+                        Key::Char('q') => break,
+                        _ => {}
+                    },
+                    PopupKind::Why => match input {
+                        Key::Char('w') | Key::Esc => app.toggle_why_menu(),
+                        Key::Down | Key::Char('j') => app.next(),
+                        Key::Up | Key::Char('k') => app.previous(),
+                        Key::Char('g') => app.top(),
+                        Key::Char('G') => app.bottom(),
+                        Key::Char('q') => break,
+                        _ => {}
+                    },
+                    // Packages in there are not dependencies which can be changed
+                    _ if app.shows_duplicates() => match input {
+                        Key::Char('q') => break,
+                        Key::Ctrl('c') => {
+                            drop(terminal);
+                            std::process::exit(0);
+                        }
+                        Key::Char('?') => app.toggle_help_menu(),
+                        Key::Esc => app.unwrap_popup(),
+                        Key::Char('w') => app.toggle_why_menu(),
+                        Key::Left | Key::Char('h') | Key::BackTab => app.tab_previous(),
+                        Key::Right | Key::Char('l') | Key::Char('\t') => app.tab_next(),
+                        Key::Down | Key::Char('j') => app.next(),
+                        Key::Up | Key::Char('k') => app.previous(),
+                        Key::Char('g') => app.top(),
+                        Key::Char('G') => app.bottom(),
+                        Key::Char('R') => reload = true,
+                        _ => {}
+                    },
                     _ => match input {
                         Key::Char('q') => {
                             break;
@@ -166,6 +320,11 @@ async fn main() -> Result<(), Box<dyn Error>> {
                         Key::Char('U') => {
                             app.toggle_show_uptodate();
                         }
+                        Key::Char('O') => {
+                            app.toggle_show_optional();
+                        }
+                        Key::Char('P') => app.toggle_show_prereleases(),
+                        Key::Char('L') => app.toggle_license_violations(),
                         Key::Char('o') => app.open_homepage(),
                         Key::Char('p') => app.open_repository(),
                         Key::Char('?') => app.toggle_help_menu(), // h is for next tab
@@ -173,16 +332,21 @@ async fn main() -> Result<(), Box<dyn Error>> {
                             app.unwrap_popup();
                         }
                         Key::Char('v') | Key::Char(' ') => app.toggle_versions_menu(),
+                        Key::Char('f') => app.toggle_features_menu(),
+                        Key::Char('t') => app.toggle_tree_menu(),
+                        Key::Char('w') => app.toggle_why_menu(),
                         Key::Left | Key::Char('h') | Key::BackTab => app.tab_previous(),
                         Key::Right | Key::Char('l') | Key::Char('\t') => app.tab_next(),
                         Key::Down | Key::Char('j') => app.next(),
                         Key::Up | Key::Char('k') => app.previous(),
+                        Key::Char('\n') if app.queue_license_check() => {
+                            app.set_message("Checking license...");
+                            install_in_next_iter = true;
+                        }
                         Key::Char('\n') => {
-                            let is_installed = app.install_dep();
-                            if is_installed {
-                                app.set_message("Dependency updated!");
-                            } else {
-                                app.set_message("Update failed.");
+                            match app.install_dep() {
+                                Ok(message) => app.set_message(&message),
+                                Err(e) => app.set_message(&e),
                             }
                             reload = true;
                         }
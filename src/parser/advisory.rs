@@ -0,0 +1,265 @@
+//! Security advisories read from local copies of advisory databases
+//!
+//! Both the RustSec advisory-db and the GitHub advisory database are git
+//! repositories which can be checked out anywhere. Reading them from disk
+//! keeps the checks working offline and away from any rate limits.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::version::{Comparator, Op, Partial};
+use super::{Version, VersionReq};
+
+/// An advisory about the versions of a package with a vulnerability
+#[derive(Debug, Clone)]
+pub struct Advisory {
+    pub id: String,
+    pub package: String,
+    pub title: String,
+    pub url: Option<String>,
+    pub aliases: Vec<String>,
+    pub severity: Option<String>,
+    /// Every version is affected if this is empty
+    affected: Vec<VersionReq>,
+    unaffected: Vec<VersionReq>,
+}
+
+impl Advisory {
+    pub fn affects(&self, version: &Version) -> bool {
+        (self.affected.is_empty() || self.affected.iter().any(|x| x.matches(version)))
+            && !self.unaffected.iter().any(|x| x.matches(version))
+    }
+}
+
+/// Every file with the extension under a folder
+fn files_in(folder: &Path, extension: &str) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let mut pending = vec![folder.to_path_buf()];
+    while let Some(folder) = pending.pop() {
+        let entries = match fs::read_dir(&folder) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|x| x.ok()) {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if matches!(path.extension(), Some(x) if x == extension) {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+#[derive(Deserialize, Debug)]
+struct RustsecAdvisory {
+    id: String,
+    package: String,
+    url: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    /// Set for notices like `unmaintained` which are not vulnerabilities
+    informational: Option<String>,
+    withdrawn: Option<String>,
+}
+#[derive(Deserialize, Debug, Default)]
+struct RustsecVersions {
+    #[serde(default)]
+    patched: Vec<String>,
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
+#[derive(Deserialize, Debug)]
+struct RustsecFile {
+    advisory: RustsecAdvisory,
+    #[serde(default)]
+    versions: RustsecVersions,
+}
+
+/// Read an advisory from the RustSec advisory-db. They are markdown files
+/// with the details in TOML at the top, followed by the title as the
+/// first heading.
+fn parse_rustsec_advisory(text: &str) -> Option<Advisory> {
+    let start = text.find("```toml")? + "```toml".len();
+    let end = start + text[start..].find("```")?;
+    let parsed: RustsecFile = toml::from_str(&text[start..end]).ok()?;
+    if parsed.advisory.informational.is_some() || parsed.advisory.withdrawn.is_some() {
+        return None;
+    }
+    let title = text[end..]
+        .lines()
+        .find(|x| x.starts_with("# "))
+        .map(|x| x[2..].trim().to_string())
+        .unwrap_or_default();
+    let unaffected = parsed
+        .versions
+        .patched
+        .iter()
+        .chain(parsed.versions.unaffected.iter())
+        .filter_map(|x| VersionReq::parse(x).ok())
+        .collect();
+    Some(Advisory {
+        id: parsed.advisory.id,
+        package: parsed.advisory.package,
+        title,
+        url: parsed.advisory.url,
+        aliases: parsed.advisory.aliases,
+        severity: None,
+        affected: vec![],
+        unaffected,
+    })
+}
+
+/// Read all the advisories in a checkout of the RustSec advisory-db
+pub fn read_rustsec(folder: &Path) -> Vec<Advisory> {
+    files_in(&folder.join("crates"), "md")
+        .iter()
+        .filter_map(|x| fs::read_to_string(x).ok())
+        .filter_map(|x| parse_rustsec_advisory(&x))
+        .collect()
+}
+
+#[derive(Deserialize, Debug)]
+struct OsvPackage {
+    ecosystem: String,
+    name: String,
+}
+#[derive(Deserialize, Debug)]
+struct OsvEvent {
+    introduced: Option<String>,
+    fixed: Option<String>,
+    last_affected: Option<String>,
+}
+#[derive(Deserialize, Debug)]
+struct OsvRange {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    events: Vec<OsvEvent>,
+}
+#[derive(Deserialize, Debug)]
+struct OsvAffected {
+    package: OsvPackage,
+    #[serde(default)]
+    ranges: Vec<OsvRange>,
+    #[serde(default)]
+    versions: Vec<String>,
+}
+#[derive(Deserialize, Debug, Default)]
+struct OsvDatabaseSpecific {
+    severity: Option<String>,
+}
+#[derive(Deserialize, Debug)]
+struct OsvReference {
+    url: String,
+}
+#[derive(Deserialize, Debug)]
+struct OsvAdvisory {
+    id: String,
+    summary: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    withdrawn: Option<String>,
+    #[serde(default)]
+    affected: Vec<OsvAffected>,
+    #[serde(default)]
+    references: Vec<OsvReference>,
+    #[serde(default)]
+    database_specific: OsvDatabaseSpecific,
+}
+
+fn osv_version(version: &str) -> Option<semver::Version> {
+    Some(Partial::parse(version)?.lower())
+}
+
+/// Turn the events of a range into requirements. Each `introduced` starts
+/// a range of affected versions which the next `fixed` or `last_affected`
+/// ends.
+fn osv_range(range: &OsvRange) -> Vec<VersionReq> {
+    let mut requirements = vec![];
+    let mut start: Option<semver::Version> = None;
+    for event in &range.events {
+        if let Some(introduced) = event.introduced.as_deref().and_then(osv_version) {
+            start = Some(introduced);
+            continue;
+        }
+        let end = match (&event.fixed, &event.last_affected) {
+            (Some(fixed), _) => osv_version(fixed).map(|x| ("<", Op::Less, x)),
+            (_, Some(last)) => osv_version(last).map(|x| ("<=", Op::LessEq, x)),
+            _ => None,
+        };
+        if let (Some(from), Some((symbol, op, to))) = (start.take(), end) {
+            let raw = format!(">= {}, {} {}", from, symbol, to);
+            let comparators = vec![
+                Comparator::new(Op::GreaterEq, from),
+                Comparator::new(op, to),
+            ];
+            requirements.push(VersionReq::new(&raw, vec![comparators]));
+        }
+    }
+    // Still affected in the latest version
+    if let Some(from) = start {
+        let raw = format!(">= {}", from);
+        let from = Comparator::new(Op::GreaterEq, from);
+        requirements.push(VersionReq::new(&raw, vec![vec![from]]));
+    }
+    requirements
+}
+
+/// Read the advisories for packages of an ecosystem from a folder of
+/// advisories in the OSV format, like the GitHub advisory database
+pub fn read_osv(folder: &Path, ecosystem: &str) -> Vec<Advisory> {
+    let mut advisories = vec![];
+    let marker = format!("\"{}\"", ecosystem);
+    for path in files_in(folder, "json") {
+        let text = match fs::read_to_string(&path) {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+        // Most of the database is about other ecosystems
+        if !text.contains(&marker) {
+            continue;
+        }
+        let parsed: OsvAdvisory = match serde_json::from_str(&text) {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+        if parsed.withdrawn.is_some() {
+            continue;
+        }
+        for affected in &parsed.affected {
+            if affected.package.ecosystem != ecosystem {
+                continue;
+            }
+            let mut requirements: Vec<VersionReq> = affected
+                .ranges
+                .iter()
+                .filter(|x| x.kind == "ECOSYSTEM" || x.kind == "SEMVER")
+                .flat_map(osv_range)
+                .collect();
+            requirements.extend(
+                affected
+                    .versions
+                    .iter()
+                    .filter_map(|x| VersionReq::parse(&format!("={}", x)).ok()),
+            );
+            if requirements.is_empty() {
+                continue;
+            }
+            advisories.push(Advisory {
+                id: parsed.id.to_string(),
+                package: affected.package.name.to_string(),
+                title: parsed.summary.clone().unwrap_or_default(),
+                url: parsed.references.first().map(|x| x.url.to_string()),
+                aliases: parsed.aliases.clone(),
+                severity: parsed.database_specific.severity.clone(),
+                affected: requirements,
+                unaffected: vec![],
+            });
+        }
+    }
+    advisories
+}
@@ -0,0 +1,176 @@
+//! Checking the licenses of dependencies against a policy
+//!
+//! The policy lives in `.depman.toml` at the root of the project:
+//!
+//! ```toml
+//! [licenses]
+//! allow = ["MIT", "Apache-2.0"]
+//! deny = ["GPL-3.0-only"]
+//! ```
+//!
+//! Licenses are SPDX expressions like `MIT OR Apache-2.0`. A dependency is
+//! fine if there is a way of picking from its alternatives that uses only
+//! allowed licenses. Denied licenses are never fine and, once there is an
+//! allow list, neither is anything missing from it. Licenses written as
+//! free text, which is common on PyPI, can't be checked and are unknown.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct LicensePolicy {
+    #[serde(default)]
+    allow: Vec<String>,
+    #[serde(default)]
+    deny: Vec<String>,
+}
+
+/// How a license does against the policy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LicenseStatus {
+    Allowed,
+    Denied,
+    /// Not an SPDX expression
+    Unknown,
+}
+
+#[derive(Deserialize, Debug)]
+struct PolicyFile {
+    licenses: Option<LicensePolicy>,
+}
+
+/// A parsed SPDX license expression
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    License(String),
+    And(Vec<Expression>),
+    Or(Vec<Expression>),
+}
+
+/// Split an expression into identifiers, operators and parentheses. A `/`
+/// is how crates.io used to write `OR`.
+fn tokenize(expression: &str) -> Vec<String> {
+    expression
+        .replace('(', " ( ")
+        .replace(')', " ) ")
+        .replace('/', " OR ")
+        .split_whitespace()
+        .map(|x| x.to_string())
+        .collect()
+}
+
+fn is_operator(token: &str, operator: &str) -> bool {
+    token.eq_ignore_ascii_case(operator)
+}
+
+fn parse_or(tokens: &[String], pos: &mut usize) -> Option<Expression> {
+    let mut terms = vec![parse_and(tokens, pos)?];
+    while *pos < tokens.len() && is_operator(&tokens[*pos], "OR") {
+        *pos += 1;
+        terms.push(parse_and(tokens, pos)?);
+    }
+    Some(if terms.len() == 1 {
+        terms.remove(0)
+    } else {
+        Expression::Or(terms)
+    })
+}
+
+fn parse_and(tokens: &[String], pos: &mut usize) -> Option<Expression> {
+    let mut terms = vec![parse_term(tokens, pos)?];
+    while *pos < tokens.len() && is_operator(&tokens[*pos], "AND") {
+        *pos += 1;
+        terms.push(parse_term(tokens, pos)?);
+    }
+    Some(if terms.len() == 1 {
+        terms.remove(0)
+    } else {
+        Expression::And(terms)
+    })
+}
+
+fn parse_term(tokens: &[String], pos: &mut usize) -> Option<Expression> {
+    let token = tokens.get(*pos)?;
+    *pos += 1;
+    if token == "(" {
+        let inner = parse_or(tokens, pos)?;
+        if tokens.get(*pos)? != ")" {
+            return None;
+        }
+        *pos += 1;
+        return Some(inner);
+    }
+    if token == ")" || is_operator(token, "AND") || is_operator(token, "OR") {
+        return None;
+    }
+    // Exceptions only ever give more permissions than the license itself
+    if *pos < tokens.len() && is_operator(&tokens[*pos], "WITH") {
+        *pos += 2;
+    }
+    Some(Expression::License(normalize_license(token)))
+}
+
+/// Write `GPL-2.0+` the way SPDX does now, as `GPL-2.0-or-later`
+fn normalize_license(license: &str) -> String {
+    match license.strip_suffix('+') {
+        Some(base) => format!("{}-or-later", base),
+        None => license.to_string(),
+    }
+}
+
+fn parse_expression(expression: &str) -> Option<Expression> {
+    let tokens = tokenize(expression);
+    let mut pos = 0;
+    let parsed = parse_or(&tokens, &mut pos)?;
+    if pos == tokens.len() {
+        Some(parsed)
+    } else {
+        None
+    }
+}
+
+impl LicensePolicy {
+    /// Read the policy of the project in `folder`, if it has one
+    pub fn load(folder: &str) -> Result<Option<LicensePolicy>, String> {
+        let text = match fs::read_to_string(Path::new(folder).join(".depman.toml")) {
+            Ok(t) => t,
+            Err(_) => return Ok(None),
+        };
+        let parsed: PolicyFile = toml::from_str(&text)
+            .map_err(|e| format!("Unable to parse {}/.depman.toml: {}", folder, e))?;
+        Ok(parsed.licenses)
+    }
+
+    fn allows_license(&self, license: &str) -> bool {
+        let listed = |list: &Vec<String>| {
+            list.iter()
+                .any(|x| normalize_license(x).eq_ignore_ascii_case(license))
+        };
+        !listed(&self.deny) && (self.allow.is_empty() || listed(&self.allow))
+    }
+
+    fn satisfies(&self, expression: &Expression) -> bool {
+        match expression {
+            Expression::License(license) => self.allows_license(license),
+            Expression::And(terms) => terms.iter().all(|x| self.satisfies(x)),
+            Expression::Or(terms) => terms.iter().any(|x| self.satisfies(x)),
+        }
+    }
+
+    /// Check a license expression. Missing licenses are only fine if
+    /// there is no allow list.
+    pub fn check(&self, license: Option<&str>) -> LicenseStatus {
+        let license = match license {
+            Some(l) => l,
+            None if self.allow.is_empty() => return LicenseStatus::Allowed,
+            None => return LicenseStatus::Denied,
+        };
+        match parse_expression(license) {
+            Some(expression) if self.satisfies(&expression) => LicenseStatus::Allowed,
+            Some(_) => LicenseStatus::Denied,
+            None => LicenseStatus::Unknown,
+        }
+    }
+}
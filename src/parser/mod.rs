@@ -1,20 +1,27 @@
+mod advisory;
+mod license;
 mod parsers;
+mod version;
 
-use futures::{future::try_join_all, stream, StreamExt};
-use semver::{Version, VersionReq};
+use futures::{stream, StreamExt};
 use std::collections::hash_map::HashMap;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::string::ToString;
+use std::sync::Arc;
 
 use crate::{events::TabItem, render::InstallCandidate};
+use license::LicensePolicy;
+pub use license::LicenseStatus;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
-pub enum ParserKind {
-    JavascriptNpm,
-    RustCargo,
-}
+pub use advisory::Advisory;
+pub use parsers::Ecosystem;
+pub use version::{Version, VersionReq};
+
+/// The package manager used by a project
+pub type ParserKind = Arc<dyn Ecosystem>;
 
 pub enum UpgradeType {
     None,
@@ -31,12 +38,143 @@ pub struct SearchDep {
     // TODO: add in more items like homepage, repo, author etc
 }
 
+/// The git reference a dependency is pinned to
+#[derive(Debug, Clone, PartialEq)]
+pub enum GitReference {
+    Rev(String),
+    Branch(String),
+    Tag(String),
+}
+
+/// Where a dependency comes from. `package` is the name of the package
+/// itself, which is different from the name used in the project for
+/// renamed dependencies.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DependencySource {
+    /// A registry other than the default one, or a renamed package
+    Registry {
+        package: String,
+        registry: Option<String>,
+    },
+    Path {
+        package: String,
+        path: String,
+    },
+    Git {
+        package: String,
+        url: String,
+        reference: Option<GitReference>,
+        locked: Option<String>,
+    },
+}
+impl DependencySource {
+    pub fn package(&self) -> &str {
+        match self {
+            DependencySource::Registry { package, .. } => package,
+            DependencySource::Path { package, .. } => package,
+            DependencySource::Git { package, .. } => package,
+        }
+    }
+
+    /// Name to look the package up with in the default registry
+    pub fn registry_name(&self) -> Option<&str> {
+        match self {
+            DependencySource::Registry {
+                package,
+                registry: None,
+            } => Some(package),
+            _ => None,
+        }
+    }
+}
+impl fmt::Display for DependencySource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DependencySource::Registry { package, registry } => match registry {
+                Some(r) => write!(f, "registry {} package {}", r, package),
+                None => write!(f, "package {}", package),
+            },
+            DependencySource::Path { path, .. } => write!(f, "path {}", path),
+            DependencySource::Git {
+                url,
+                reference,
+                locked,
+                ..
+            } => {
+                write!(f, "git {}", url)?;
+                match reference {
+                    Some(GitReference::Rev(r)) => write!(f, " rev {}", r)?,
+                    Some(GitReference::Branch(b)) => write!(f, " branch {}", b)?,
+                    Some(GitReference::Tag(t)) => write!(f, " tag {}", t)?,
+                    None => {}
+                }
+                if let Some(commit) = locked {
+                    write!(f, " @ {}", &commit[..commit.len().min(7)])?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The oldest version of the compiler or runtime a project supports, like
+/// `rust-version` in Cargo.toml or `engines.node` in package.json
+#[derive(Debug, Clone)]
+pub struct Toolchain {
+    pub name: String,
+    pub version: Version,
+}
+impl fmt::Display for Toolchain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.version)
+    }
+}
+
+/// Why a version of a package should not be used any more
+#[derive(Debug, Clone, PartialEq)]
+pub enum Withdrawal {
+    /// Pulled from the registry, as with `cargo yank`
+    Yanked,
+    /// Still available but deprecated, with the message given for it
+    Deprecated(String),
+}
+impl fmt::Display for Withdrawal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Withdrawal::Yanked => write!(f, "yanked"),
+            Withdrawal::Deprecated(_) => write!(f, "deprecated"),
+        }
+    }
+}
+
+/// The features of a package along with the features each of them enables
+pub type FeatureList = BTreeMap<String, Vec<String>>;
+
+/// The features a project turns on for one of its dependencies, and
+/// whether the dependency itself is only pulled in by some feature
+#[derive(Debug, Clone, PartialEq)]
+pub struct DependencyFeatures {
+    pub enabled: Vec<String>,
+    pub default_features: bool,
+    pub optional: bool,
+    /// Features of the project which turn on an optional dependency,
+    /// directly or through other features
+    pub enabled_by: Vec<String>,
+}
+
 type DependencyGroup = BTreeMap<String, Option<VersionReq>>;
 #[derive(Clone)]
 pub struct Config {
     pub name: Option<String>,
     pub version: Option<Version>,
     pub groups: BTreeMap<String, DependencyGroup>,
+    /// Dependencies in each group which are not from the default registry
+    /// under the name used for them in the project
+    pub sources: HashMap<String, HashMap<String, DependencySource>>,
+    /// Features of the dependencies in each group, for package managers
+    /// which have them
+    pub features: HashMap<String, HashMap<String, DependencyFeatures>>,
+    pub toolchain: Option<Toolchain>,
 }
 #[derive(Debug, Clone)]
 pub struct DepInfo {
@@ -47,14 +185,67 @@ pub struct DepInfo {
     license: Option<String>,
     description: Option<String>,
     versions: Vec<Version>,
+    features: HashMap<String, FeatureList>, // by version
+    withdrawn: HashMap<String, Withdrawal>, // by version
+    tags: HashMap<String, Vec<String>>,     // by version
+    /// Versions of the toolchain each version of the package works with
+    toolchain: HashMap<String, VersionReq>, // by version
+    /// For registries where the license can change between versions
+    licenses: HashMap<String, String>, // by version
+}
+/// All the versions of each package in the lockfile. Where the lockfile
+/// tells which copy the project itself uses, that one comes first.
+type Lockfile = HashMap<String, Vec<Version>>;
+/// A single copy of a package in the lockfile
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+}
+impl fmt::Display for LockedPackage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.version)
+    }
+}
+/// The copies of other packages each package in the lockfile uses
+type DependencyGraph = HashMap<LockedPackage, Vec<LockedPackage>>;
+/// How a package ends up in the project, from a dependency in one of the
+/// groups down to the package itself
+#[derive(Debug, Clone)]
+pub struct DependencyPath {
+    pub group: String,
+    pub packages: Vec<LockedPackage>,
+}
+/// A package locked at more than one version
+#[derive(Debug, Clone)]
+pub struct Duplicate {
+    pub name: String,
+    pub copies: Vec<DuplicateCopy>, // newest first
+}
+#[derive(Debug, Clone)]
+pub struct DuplicateCopy {
+    pub version: String,
+    /// Dependencies of the project which pull this copy in
+    pub dependents: Vec<LockedPackage>,
+    /// Upgrades of those dependencies which could move them to the newest
+    /// copy, along with the version to upgrade to
+    pub upgrades: Vec<(LockedPackage, Version)>,
+}
+impl fmt::Display for DependencyPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let packages: Vec<String> = self.packages.iter().map(|x| x.to_string()).collect();
+        write!(f, "[{}] {}", self.group, packages.join(" -> "))
+    }
 }
-type Lockfile = HashMap<String, Version>;
 type MetaData = HashMap<String, DepInfo>;
 #[derive(Clone)]
 pub struct Project {
     config: Config,
     lockfile: Lockfile,
+    graph: DependencyGraph,
     metadata: MetaData,
+    advisories: HashMap<String, Vec<Advisory>>, // by package
+    license_policy: Result<Option<LicensePolicy>, String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -83,82 +274,121 @@ pub fn stringify<T: ToString>(value: &Option<T>) -> String {
     }
 }
 
+/// Drop repeated versions of a package, keeping the first of each
+fn dedup_lockfile(lockfile: &mut Lockfile) {
+    for versions in lockfile.values_mut() {
+        let mut seen = vec![];
+        versions.retain(|x| {
+            let new = !seen.contains(x);
+            seen.push(x.clone());
+            new
+        });
+    }
+}
+
+/// Keep the dependencies of each package in order, without repeats
+fn sort_graph(graph: &mut DependencyGraph) {
+    for dependencies in graph.values_mut() {
+        dependencies.sort();
+        dependencies.dedup();
+    }
+}
+
+/// How far `current` is behind, given the newest version in its range
+/// and the newest one overall
+fn upgrade_type(current: &Version, semver: &Version, latest: &Version) -> UpgradeType {
+    if current.major < semver.major {
+        UpgradeType::Major
+    } else if current.minor < semver.minor {
+        UpgradeType::Minor
+    } else if current.patch < semver.patch {
+        UpgradeType::Patch
+    } else if latest > current {
+        UpgradeType::Breaking
+    } else {
+        UpgradeType::None
+    }
+}
+
+/// Names of all the dependencies in the default registry
+fn registry_names(config: &Config) -> Vec<String> {
+    let mut names: Vec<String> = config
+        .groups
+        .iter()
+        .flat_map(|(group, deps)| deps.keys().map(move |x| (group, x)))
+        .filter_map(
+            |(group, x)| match config.sources.get(group).and_then(|s| s.get(x)) {
+                Some(source) => source.registry_name(),
+                None => Some(x),
+            },
+        )
+        .map(|x| x.to_string())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
 // Mostly for derived values
 impl Project {
     pub fn determine_kind(folder: &str) -> Option<ParserKind> {
-        if parsers::is_this_it(folder, &ParserKind::JavascriptNpm) {
-            Some(ParserKind::JavascriptNpm)
-        } else if parsers::is_this_it(folder, &ParserKind::RustCargo) {
-            Some(ParserKind::RustCargo)
-        } else {
-            None
-        }
-    }
-    pub async fn parse(folder: &str, kind: &ParserKind) -> Project {
-        let config = parsers::parse_config(folder, kind);
-        let lockfile = parsers::parse_lockfile(folder, kind);
-        let dep_names: Vec<String> = config
-            .groups
-            .keys()
-            .flat_map(|x| config.groups[x].keys())
-            .map(|x| x.to_string())
-            .collect();
-
-        let fetchers = dep_names
-            .clone()
+        parsers::ecosystems()
             .into_iter()
-            .map(|x| parsers::fetch_dep_info(x, kind))
-            .collect::<Vec<_>>();
-
-        let mut metadata = HashMap::new();
-        let mut st = stream::iter(fetchers).buffer_unordered(10);
-        while let Some(chunk) = st.next().await {
-            if let Ok(mut item) = chunk {
-                item.versions.sort();
-                item.versions = item.versions.into_iter().rev().collect();
-                metadata.insert(item.name.to_string(), item);
-            }
+            .find(|x| x.is_this_it(folder))
+    }
+    /// Read the project without fetching anything about its dependencies
+    pub fn parse_local(folder: &str, kind: &ParserKind) -> Project {
+        let config = kind.parse_config(folder);
+        let mut lockfile = kind.parse_lockfile(folder).unwrap_or_default();
+        dedup_lockfile(&mut lockfile);
+        let mut graph = kind.parse_dependency_graph(folder).unwrap_or_default();
+        sort_graph(&mut graph);
+        let mut advisories: HashMap<String, Vec<Advisory>> = HashMap::new();
+        for advisory in kind.load_advisories() {
+            advisories
+                .entry(advisory.package.to_string())
+                .or_default()
+                .push(advisory);
         }
-
         Project {
             config,
             lockfile,
-            metadata,
+            graph,
+            metadata: HashMap::new(),
+            advisories,
+            license_policy: LicensePolicy::load(folder),
         }
     }
 
+    pub async fn parse(folder: &str, kind: &ParserKind) -> Project {
+        let mut project = Project::parse_local(folder, kind);
+        let dep_names = registry_names(&project.config);
+        project.fetch_package_metadata(kind, &dep_names).await;
+        project
+    }
+
     pub async fn reparse(&self, folder: &str, kind: &ParserKind) -> Project {
-        let config = parsers::parse_config(folder, kind);
-        let lockfile = parsers::parse_lockfile(folder, kind);
+        let config = kind.parse_config(folder);
+        let mut lockfile = kind.parse_lockfile(folder).unwrap_or_default();
+        dedup_lockfile(&mut lockfile);
+        let mut graph = kind.parse_dependency_graph(folder).unwrap_or_default();
+        sort_graph(&mut graph);
 
-        let dep_names: Vec<String> = config
-            .groups
-            .keys()
-            .flat_map(|x| config.groups[x].keys())
-            .map(|x| x.to_string())
+        let dep_names: Vec<String> = registry_names(&config)
+            .into_iter()
             .filter(|x| !self.metadata.keys().any(|e| e == x))
             .collect();
 
-        let fetchers = dep_names
-            .clone()
-            .into_iter()
-            .map(|x| parsers::fetch_dep_info(x, kind));
-        let results = try_join_all(fetchers).await.unwrap_or_default();
-        let mut metadata = HashMap::new();
-        if results.len() == dep_names.len() {
-            for (count, item) in dep_names.into_iter().enumerate() {
-                let mut api_data = results[count].clone();
-                api_data.versions.sort();
-                api_data.versions = api_data.versions.into_iter().rev().collect();
-                metadata.insert(item, api_data);
-            }
-        }
-        metadata.extend(self.metadata.clone());
-        Project {
+        let mut project = Project {
             config,
             lockfile,
-            metadata,
-        }
+            graph,
+            metadata: self.metadata.clone(),
+            advisories: self.advisories.clone(),
+            license_policy: LicensePolicy::load(folder),
+        };
+        project.fetch_package_metadata(kind, &dep_names).await;
+        project
     }
 
     // pub async fn search_deps(kind: &ParserKind, query: &str) {}
@@ -185,12 +415,41 @@ impl Project {
         deps
     }
 
-    pub fn is_versions_available(&self, name: &str) -> bool {
-        self.metadata.get(name).is_some()
+    pub fn get_source(&self, group: &str, name: &str) -> Option<&DependencySource> {
+        self.config.sources.get(group)?.get(name)
+    }
+
+    /// Name of the package itself, which differs from `name` if renamed
+    fn get_package_name<'a>(&'a self, group: &str, name: &'a str) -> &'a str {
+        self.get_source(group, name)
+            .map(|x| x.package())
+            .unwrap_or(name)
+    }
+
+    /// Name a dependency is locked under. Some lockfiles list renamed
+    /// packages under their own name, like Cargo.lock, others under the
+    /// name they are installed as, like package-lock.json.
+    fn get_locked_name<'a>(&'a self, group: &str, name: &'a str) -> &'a str {
+        if self.lockfile.contains_key(name) {
+            name
+        } else {
+            self.get_package_name(group, name)
+        }
     }
 
-    pub fn get_dep_versions(&self, name: &str) -> Option<&Vec<Version>> {
-        if let Some(meta) = &self.metadata.get(name) {
+    fn get_metadata(&self, group: &str, name: &str) -> Option<&DepInfo> {
+        match self.get_source(group, name) {
+            Some(source) => self.metadata.get(source.registry_name()?),
+            None => self.metadata.get(name),
+        }
+    }
+
+    pub fn is_versions_available(&self, group: &str, name: &str) -> bool {
+        self.get_metadata(group, name).is_some()
+    }
+
+    pub fn get_dep_versions(&self, group: &str, name: &str) -> Option<&Vec<Version>> {
+        if let Some(meta) = &self.get_metadata(group, name) {
             Some(&meta.versions)
         // Some(meta.versions.clone().into_iter().map(|x| x.to_string()).collect())
         } else {
@@ -198,13 +457,24 @@ impl Project {
         }
     }
 
-    pub fn get_current_version(&self, name: &str) -> Option<&Version> {
-        self.lockfile.get(name)
+    /// All the versions of a package in the lockfile
+    pub fn get_locked_versions(&self, group: &str, name: &str) -> Option<&Vec<Version>> {
+        self.lockfile.get(self.get_locked_name(group, name))
+    }
+
+    /// The locked version a dependency resolved to, which is the first one
+    /// matching its requirement
+    pub fn get_current_version(&self, group: &str, name: &str) -> Option<&Version> {
+        let versions = self.get_locked_versions(group, name)?;
+        let requirement = self.config.groups.get(group)?.get(name)?.as_ref();
+        requirement
+            .and_then(|r| versions.iter().find(|x| r.matches(x)))
+            .or_else(|| versions.first())
     }
     pub fn get_semver_version(&self, group: &str, name: &str) -> Option<&Version> {
-        let current_version = self.get_current_version(&name);
+        let current_version = self.get_current_version(group, name);
         let specified_version = self.get_specified_version(&group, &name);
-        let versions = self.get_dep_versions(&name);
+        let versions = self.get_dep_versions(group, name);
         if let Some(sv) = specified_version {
             if let Some(vers) = versions {
                 if let Some(cv) = current_version {
@@ -213,8 +483,14 @@ impl Project {
                         let mut last = cv;
                         for i in (0..cp).rev() {
                             if sv.matches(&vers[i]) {
-                                last = &vers[i];
-                            } else {
+                                if self
+                                    .get_withdrawal(group, name, &vers[i].to_string())
+                                    .is_none()
+                                {
+                                    last = &vers[i];
+                                }
+                            } else if vers[i].pre.is_empty() {
+                                // Pre-releases in between do not end the range
                                 break;
                             }
                         }
@@ -234,65 +510,561 @@ impl Project {
             .unwrap()
             .as_ref()
     }
-    pub fn get_latest_version(&self, name: &str) -> Option<&Version> {
-        let versions = self.get_dep_versions(&name);
-        match versions {
-            Some(v) => Some(&v[0]),
-            None => None,
+    /// Check if pre-releases are worth suggesting for a dependency, which
+    /// is when its requirement already targets one
+    pub fn allows_prerelease(&self, group: &str, name: &str) -> bool {
+        matches!(self.get_specified_version(group, name), Some(r) if r.has_prerelease())
+    }
+
+    /// Check if a version is worth suggesting, which it is not if it is
+    /// yanked, deprecated or, unless the dependency asks for them, a
+    /// pre-release
+    fn is_suggestible(&self, group: &str, name: &str, version: &Version) -> bool {
+        (version.pre.is_empty() || self.allows_prerelease(group, name))
+            && self
+                .get_withdrawal(group, name, &version.to_string())
+                .is_none()
+    }
+
+    pub fn get_latest_version(&self, group: &str, name: &str) -> Option<&Version> {
+        let versions = self.get_dep_versions(group, name)?;
+        versions
+            .iter()
+            .find(|x| self.is_suggestible(group, name, x))
+    }
+
+    pub fn get_toolchain(&self) -> Option<&Toolchain> {
+        self.config.toolchain.as_ref()
+    }
+
+    /// Check if a version of a package works with the oldest toolchain the
+    /// project supports, assuming it does if either is unknown
+    pub fn is_compatible(&self, group: &str, name: &str, version: &Version) -> bool {
+        let toolchain = match self.get_toolchain() {
+            Some(t) => &t.version,
+            None => return true,
+        };
+        match self
+            .get_metadata(group, name)
+            .and_then(|x| x.toolchain.get(&version.to_string()))
+        {
+            Some(requirement) => requirement.matches(toolchain),
+            None => true,
         }
     }
 
+    /// The newest version which works with the toolchain of the project
+    pub fn get_compatible_version(&self, group: &str, name: &str) -> Option<&Version> {
+        let versions = self.get_dep_versions(group, name)?;
+        versions
+            .iter()
+            .find(|x| self.is_suggestible(group, name, x) && self.is_compatible(group, name, x))
+    }
+
+    /// Why a version of a package has been yanked or deprecated, if it has
+    pub fn get_withdrawal(&self, group: &str, name: &str, version: &str) -> Option<&Withdrawal> {
+        self.get_metadata(group, name)?.withdrawn.get(version)
+    }
+
+    /// Registry tags like npm dist-tags pointing at a version
+    pub fn get_version_tags(&self, group: &str, name: &str, version: &str) -> Option<&Vec<String>> {
+        self.get_metadata(group, name)?.tags.get(version)
+    }
+
+    /// A warning if the version a dependency resolved to has been yanked
+    /// or deprecated
+    pub fn get_current_version_warning(&self, group: &str, name: &str) -> Option<String> {
+        let current = self.get_current_version(group, name)?;
+        Some(
+            match self.get_withdrawal(group, name, &current.to_string())? {
+                Withdrawal::Deprecated(message) if !message.is_empty() => {
+                    format!("{} is deprecated: {}", current, message)
+                }
+                withdrawal => format!("{} is {}", current, withdrawal),
+            },
+        )
+    }
+
     pub fn get_upgrade_type(&self, group: &str, name: &str) -> UpgradeType {
-        let current_version = self.get_current_version(&name);
+        let current_version = self.get_current_version(group, name);
         let semver_version = self.get_semver_version(&group, &name);
-        let latest_version = self.get_latest_version(&name);
+        let latest_version = self.get_latest_version(group, name);
         if let Some(cv) = &current_version {
             if let Some(sv) = &semver_version {
                 if let Some(lv) = &latest_version {
-                    if cv.major < sv.major {
-                        return UpgradeType::Major;
-                    } else if cv.minor < sv.minor {
-                        return UpgradeType::Minor;
-                    } else if cv.patch < sv.patch {
-                        return UpgradeType::Patch;
-                    } else if lv > cv {
-                        return UpgradeType::Breaking;
-                    }
+                    return upgrade_type(cv, sv, lv);
                 }
             }
         }
         UpgradeType::None
     }
 
-    pub fn get_author(&self, name: &str) -> Option<String> {
-        let author = &self.metadata.get(name)?.author;
+    /// The copy of a package in the lockfile a dependency resolved to
+    pub fn get_locked_package(&self, group: &str, name: &str) -> Option<LockedPackage> {
+        Some(LockedPackage {
+            name: self.get_locked_name(group, name).to_string(),
+            version: self.get_current_version(group, name)?.to_string(),
+        })
+    }
+
+    /// The packages a copy of a package in the lockfile depends on
+    pub fn get_locked_dependencies(&self, package: &LockedPackage) -> Option<&Vec<LockedPackage>> {
+        self.graph.get(package)
+    }
+
+    /// Packages in the lockfile which depend on any of `packages`,
+    /// directly or through others, along with `packages` themselves
+    fn get_dependents<'a>(
+        &'a self,
+        packages: Vec<&'a LockedPackage>,
+    ) -> HashSet<&'a LockedPackage> {
+        let mut parents: HashMap<&LockedPackage, Vec<&LockedPackage>> = HashMap::new();
+        for (package, dependencies) in &self.graph {
+            for dependency in dependencies {
+                parents.entry(dependency).or_default().push(package);
+            }
+        }
+        let mut dependents: HashSet<&LockedPackage> = packages.iter().copied().collect();
+        let mut pending = packages;
+        while let Some(package) = pending.pop() {
+            for parent in parents.get(package).into_iter().flatten() {
+                if dependents.insert(parent) {
+                    pending.push(parent);
+                }
+            }
+        }
+        dependents
+    }
+
+    /// The copies in the lockfile the dependencies of the project resolved
+    /// to, across all groups
+    fn get_direct_packages(&self) -> Vec<LockedPackage> {
+        let mut packages: Vec<LockedPackage> = self
+            .config
+            .groups
+            .iter()
+            .flat_map(|(group, deps)| deps.keys().map(move |x| (group, x)))
+            .filter_map(|(group, name)| self.get_locked_package(group, name))
+            .collect();
+        packages.sort();
+        packages.dedup();
+        packages
+    }
+
+    /// Advisories about the version of a package in the lockfile
+    pub fn get_package_advisories(&self, package: &LockedPackage) -> Vec<&Advisory> {
+        let version = match Version::parse(&package.version) {
+            Ok(v) => v,
+            Err(_) => return vec![],
+        };
+        self.advisories
+            .get(&package.name)
+            .into_iter()
+            .flatten()
+            .filter(|x| x.affects(&version))
+            .collect()
+    }
+
+    /// Advisories about a dependency or any of the packages it pulls in
+    pub fn get_vulnerabilities(&self, group: &str, name: &str) -> Vec<(LockedPackage, &Advisory)> {
+        let mut vulnerabilities = vec![];
+        if self.advisories.is_empty() {
+            return vulnerabilities;
+        }
+        let root = match self.get_locked_package(group, name) {
+            Some(r) => r,
+            None => return vulnerabilities,
+        };
+        let mut seen: HashSet<LockedPackage> = HashSet::new();
+        let mut pending = vec![root];
+        while let Some(package) = pending.pop() {
+            if !seen.insert(package.clone()) {
+                continue;
+            }
+            for advisory in self.get_package_advisories(&package) {
+                vulnerabilities.push((package.clone(), advisory));
+            }
+            pending.extend(self.graph.get(&package).into_iter().flatten().cloned());
+        }
+        vulnerabilities
+    }
+
+    /// The oldest version newer than the one in use which none of the
+    /// advisories about a dependency affect, if the one in use is affected
+    pub fn get_patched_version(&self, group: &str, name: &str) -> Option<&Version> {
+        let current = self.get_current_version(group, name)?;
+        let advisories = self.advisories.get(self.get_package_name(group, name))?;
+        if !advisories.iter().any(|x| x.affects(current)) {
+            return None;
+        }
+        self.get_dep_versions(group, name)?.iter().rev().find(|x| {
+            *x > current
+                && self.is_suggestible(group, name, x)
+                && !advisories.iter().any(|a| a.affects(x))
+        })
+    }
+
+    /// Every way copies of a package end up in the project, like
+    /// `cargo tree -i` or `npm why`
+    pub fn get_dependency_paths(&self, name: &str) -> Vec<DependencyPath> {
+        // Only the packages leading to the one asked for are worth walking
+        let leading = self.get_dependents(self.graph.keys().filter(|x| x.name == name).collect());
+
+        let mut paths = vec![];
+        for (group, deps) in &self.config.groups {
+            for dep in deps.keys() {
+                if let Some(root) = self.get_locked_package(group, dep) {
+                    if leading.contains(&root) {
+                        let mut path = DependencyPath {
+                            group: group.to_string(),
+                            packages: vec![root],
+                        };
+                        self.collect_dependency_paths(name, &leading, &mut path, &mut paths);
+                    }
+                }
+            }
+        }
+        paths
+    }
+
+    /// Packages locked at more than one version, with the dependencies of
+    /// the project behind each copy
+    pub fn get_duplicates(&self) -> Vec<Duplicate> {
+        let direct = self.get_direct_packages();
+        let mut duplicates = vec![];
+        for (name, versions) in &self.lockfile {
+            if versions.len() < 2 {
+                continue;
+            }
+            let mut versions = versions.clone();
+            versions.sort();
+            versions.reverse();
+            let newest = LockedPackage {
+                name: name.to_string(),
+                version: versions[0].to_string(),
+            };
+            let copies = versions
+                .iter()
+                .map(|version| {
+                    let package = LockedPackage {
+                        name: name.to_string(),
+                        version: version.to_string(),
+                    };
+                    let dependents = self.get_dependents(vec![&package]);
+                    let dependents: Vec<LockedPackage> = direct
+                        .iter()
+                        .filter(|x| dependents.contains(x))
+                        .cloned()
+                        .collect();
+                    let upgrades = if package == newest {
+                        vec![]
+                    } else {
+                        dependents
+                            .iter()
+                            .filter_map(|x| self.get_duplicate_upgrade(x, &newest))
+                            .collect()
+                    };
+                    DuplicateCopy {
+                        version: version.to_string(),
+                        dependents,
+                        upgrades,
+                    }
+                })
+                .collect();
+            duplicates.push(Duplicate {
+                name: name.to_string(),
+                copies,
+            });
+        }
+        duplicates.sort_by(|a, b| a.name.cmp(&b.name));
+        duplicates
+    }
+
+    /// The version to upgrade a dependency of the project to so that it can
+    /// move off an older copy of a duplicated package. Only the versions of
+    /// packages are known and not what they depend on, so for anything
+    /// other than the package itself this is the latest version, which may
+    /// or may not use the newest copy.
+    fn get_duplicate_upgrade(
+        &self,
+        dependent: &LockedPackage,
+        duplicate: &LockedPackage,
+    ) -> Option<(LockedPackage, Version)> {
+        let current = Version::parse(&dependent.version).ok()?;
+        let target = if dependent.name == duplicate.name {
+            Version::parse(&duplicate.version).ok()?
+        } else {
+            self.get_package_latest_version(dependent)?.clone()
+        };
+        if target > current {
+            Some((dependent.clone(), target))
+        } else {
+            None
+        }
+    }
+
+    fn collect_dependency_paths(
+        &self,
+        name: &str,
+        leading: &HashSet<&LockedPackage>,
+        path: &mut DependencyPath,
+        paths: &mut Vec<DependencyPath>,
+    ) {
+        let last = match path.packages.last() {
+            Some(l) => l,
+            None => return,
+        };
+        if last.name == name {
+            paths.push(path.clone());
+            return;
+        }
+        for dependency in self.graph.get(last).into_iter().flatten() {
+            if leading.contains(dependency) && !path.packages.contains(dependency) {
+                path.packages.push(dependency.clone());
+                self.collect_dependency_paths(name, leading, path, paths);
+                path.packages.pop();
+            }
+        }
+    }
+
+    /// Check if the versions of a package only found in the lockfile have
+    /// been fetched
+    pub fn has_package_metadata(&self, name: &str) -> bool {
+        self.metadata.contains_key(name)
+    }
+
+    /// Fetch the details and versions of packages from the registry
+    pub async fn fetch_package_metadata(&mut self, kind: &ParserKind, names: &[String]) {
+        let fetchers = names
+            .iter()
+            .map(|x| kind.fetch_dep_info(x))
+            .collect::<Vec<_>>();
+
+        let mut st = stream::iter(fetchers).buffer_unordered(10);
+        while let Some(chunk) = st.next().await {
+            if let Ok(mut item) = chunk {
+                item.versions.sort();
+                item.versions = item.versions.into_iter().rev().collect();
+                self.metadata.insert(item.name.to_string(), item);
+            }
+        }
+    }
+
+    /// The newest version of a package in the lockfile which is neither
+    /// withdrawn nor a pre-release
+    pub fn get_package_latest_version(&self, package: &LockedPackage) -> Option<&Version> {
+        let meta = self.metadata.get(&package.name)?;
+        meta.versions
+            .iter()
+            .find(|x| x.pre.is_empty() && !meta.withdrawn.contains_key(&x.to_string()))
+    }
+
+    /// The newest version a copy of a package in the lockfile can be
+    /// upgraded to. Nothing says which range it was picked from, so the
+    /// versions compatible with it are the ones it can be upgraded to.
+    pub fn get_package_semver_version(&self, package: &LockedPackage) -> Option<&Version> {
+        let meta = self.metadata.get(&package.name)?;
+        let current = Version::parse(&package.version).ok()?;
+        let requirement = VersionReq::parse(&format!("^{}", package.version)).ok()?;
+        meta.versions
+            .iter()
+            .filter(|x| x.pre.is_empty() || !current.pre.is_empty())
+            .filter(|x| !meta.withdrawn.contains_key(&x.to_string()))
+            .find(|x| requirement.matches(x))
+    }
+
+    pub fn get_package_upgrade_type(&self, package: &LockedPackage) -> UpgradeType {
+        let current = match Version::parse(&package.version) {
+            Ok(v) => v,
+            Err(_) => return UpgradeType::None,
+        };
+        let semver_version = self.get_package_semver_version(package);
+        match self.get_package_latest_version(package) {
+            Some(lv) => upgrade_type(&current, semver_version.unwrap_or(&current), lv),
+            None => UpgradeType::None,
+        }
+    }
+
+    pub fn get_features(&self, group: &str, name: &str) -> Option<&DependencyFeatures> {
+        self.config.features.get(group)?.get(name)
+    }
+
+    pub fn is_optional(&self, group: &str, name: &str) -> bool {
+        matches!(self.get_features(group, name), Some(f) if f.optional)
+    }
+
+    /// Check if a dependency is part of a build with the default features
+    /// of the project
+    pub fn is_default_build(&self, group: &str, name: &str) -> bool {
+        match self.get_features(group, name) {
+            Some(f) => !f.optional || f.enabled_by.iter().any(|x| x == "default"),
+            None => true,
+        }
+    }
+
+    /// Features the package has in the version in use, or the latest
+    /// version if it is not locked yet
+    pub fn get_available_features(&self, group: &str, name: &str) -> Option<&FeatureList> {
+        let version = self
+            .get_current_version(group, name)
+            .or_else(|| self.get_latest_version(group, name))?;
+        self.get_metadata(group, name)?
+            .features
+            .get(&version.to_string())
+    }
+
+    pub fn get_author(&self, group: &str, name: &str) -> Option<String> {
+        let author = &self.get_metadata(group, name)?.author;
         match author {
             Some(a) => Some(a.to_string()),
             None => None,
         }
     }
-    pub fn get_homepage(&self, name: &str) -> Option<String> {
-        self.metadata.get(name)?.homepage.clone()
+    pub fn get_homepage(&self, group: &str, name: &str) -> Option<String> {
+        self.get_metadata(group, name)?.homepage.clone()
+    }
+    pub fn get_repository(&self, group: &str, name: &str) -> Option<String> {
+        self.get_metadata(group, name)?.repository.clone()
+    }
+    /// The license of a version of a package, which falls back to the one
+    /// of the package for registries which only have that
+    fn get_version_license(&self, group: &str, name: &str, version: &str) -> Option<&str> {
+        let meta = self.get_metadata(group, name)?;
+        meta.licenses
+            .get(version)
+            .or(meta.license.as_ref())
+            .map(|x| x.as_str())
+    }
+    /// The license of the version of a dependency in use, or the latest
+    /// version if it is not locked yet
+    pub fn get_license(&self, group: &str, name: &str) -> Option<String> {
+        let version = self
+            .get_current_version(group, name)
+            .or_else(|| self.get_latest_version(group, name));
+        match version {
+            Some(v) => self.get_version_license(group, name, &v.to_string()),
+            None => self.get_metadata(group, name)?.license.as_deref(),
+        }
+        .map(|x| x.to_string())
+    }
+    pub fn has_license_policy(&self) -> bool {
+        matches!(self.license_policy, Ok(Some(_)))
     }
-    pub fn get_repository(&self, name: &str) -> Option<String> {
-        self.metadata.get(name)?.repository.clone()
+    /// Why the license policy in .depman.toml could not be read
+    pub fn get_license_policy_error(&self) -> Option<&str> {
+        self.license_policy.as_ref().err().map(|x| x.as_str())
     }
-    pub fn get_license(&self, name: &str) -> Option<String> {
-        self.metadata.get(name)?.license.clone()
+    /// How the license of a dependency does against the license policy.
+    /// Only packages from the registry have a license to check.
+    pub fn get_license_status(&self, group: &str, name: &str) -> Option<LicenseStatus> {
+        match &self.license_policy {
+            Ok(Some(policy)) if self.is_versions_available(group, name) => {
+                Some(policy.check(self.get_license(group, name).as_deref()))
+            }
+            _ => None,
+        }
     }
-    pub fn get_description(&self, name: &str) -> Option<String> {
-        self.metadata.get(name)?.description.clone()
+    pub fn violates_license_policy(&self, group: &str, name: &str) -> bool {
+        self.get_license_status(group, name) == Some(LicenseStatus::Denied)
+    }
+    /// Dependencies in each group whose licenses have the status, along
+    /// with their licenses
+    pub fn get_licenses_with_status(
+        &self,
+        status: LicenseStatus,
+    ) -> Vec<(String, String, Option<String>)> {
+        let mut found = vec![];
+        for (group, deps) in &self.config.groups {
+            for name in deps.keys() {
+                if self.get_license_status(group, name) == Some(status) {
+                    found.push((
+                        group.to_string(),
+                        name.to_string(),
+                        self.get_license(group, name),
+                    ));
+                }
+            }
+        }
+        found
+    }
+    /// Dependencies from the registry whose licenses could not be checked
+    /// because fetching their metadata failed
+    pub fn get_unchecked_licenses(&self) -> Vec<(String, String)> {
+        let mut found = vec![];
+        for (group, deps) in &self.config.groups {
+            for name in deps.keys() {
+                let from_registry = self
+                    .get_source(group, name)
+                    .is_none_or(|x| x.registry_name().is_some());
+                if from_registry && !self.is_versions_available(group, name) {
+                    found.push((group.to_string(), name.to_string()));
+                }
+            }
+        }
+        found
+    }
+    /// Check the license of a version about to be installed against the
+    /// license policy. Packages found by searching are fetched first.
+    pub async fn check_license(
+        &mut self,
+        kind: &ParserKind,
+        candidate: &InstallCandidate,
+    ) -> Result<(), String> {
+        let policy = match &self.license_policy {
+            Ok(Some(p)) => p.clone(),
+            _ => return Ok(()),
+        };
+        if self
+            .get_metadata(&candidate.kind, &candidate.name)
+            .is_none()
+        {
+            self.fetch_package_metadata(kind, &[candidate.name.to_string()])
+                .await;
+        }
+        let license =
+            self.get_version_license(&candidate.kind, &candidate.name, &candidate.version);
+        // Unknown licenses are let through on purpose, the list of
+        // dependencies marks them for checking by hand instead
+        if policy.check(license) != LicenseStatus::Denied {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} {} is licensed under {}, which the license policy does not allow",
+                candidate.name,
+                candidate.version,
+                license.unwrap_or("an unknown license")
+            ))
+        }
+    }
+    pub fn get_description(&self, group: &str, name: &str) -> Option<String> {
+        self.get_metadata(group, name)?.description.clone()
     }
 
     pub fn delete_dep(&self, kind: &ParserKind, folder: &str, group: &str, name: &str) -> bool {
-        parsers::delete_dep(kind, folder, group, name)
+        kind.delete_dep(folder, group, name).is_ok()
+    }
+
+    pub fn install_dep(
+        &self,
+        kind: &ParserKind,
+        folder: &str,
+        dep: InstallCandidate,
+    ) -> Result<(), String> {
+        kind.install_dep(dep, folder).map_err(|e| e.to_string())
     }
 
-    pub fn install_dep(&self, kind: &ParserKind, folder: &str, dep: InstallCandidate) -> bool {
-        parsers::install_dep(kind, folder, dep)
+    pub fn update_features(
+        &self,
+        kind: &ParserKind,
+        folder: &str,
+        group: &str,
+        name: &str,
+        features: &DependencyFeatures,
+    ) -> bool {
+        kind.update_features(folder, group, name, features).is_ok()
     }
 
     pub async fn search_dep(&self, kind: &ParserKind, term: &str) -> Option<Vec<SearchDep>> {
-        parsers::search_dep(kind, term).await.ok()
+        kind.search_dep(term).await.ok()
     }
 }
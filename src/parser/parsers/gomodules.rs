@@ -0,0 +1,573 @@
+use std::collections::hash_map::HashMap;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    parser::{
+        version::{Comparator, Op},
+        Config, DepInfo, DependencyGroup, DependencySource, Lockfile, SearchDep, Version,
+        VersionReq,
+    },
+    render::InstallCandidate,
+};
+
+use super::Ecosystem;
+
+/// Response from `<module>/@latest` on the module proxy
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct LatestResponse {
+    #[serde(rename = "Version")]
+    version: String,
+}
+
+/// A directive from go.mod, either on a line of its own or as an entry in
+/// a `verb ( ... )` block
+#[derive(Debug, Clone)]
+struct Entry {
+    verb: String,
+    args: Vec<String>,
+    /// Trailing comment including the `//`
+    comment: Option<String>,
+    line: usize,
+    /// Index of the block the entry is in
+    block: Option<usize>,
+}
+
+impl Entry {
+    fn is_indirect(&self) -> bool {
+        match &self.comment {
+            Some(c) => {
+                let c = c.trim_start_matches('/').trim();
+                c == "indirect" || c.starts_with("indirect;")
+            }
+            None => false,
+        }
+    }
+}
+
+/// A `verb ( ... )` block with the lines it starts and ends on
+#[derive(Debug, Clone)]
+struct Block {
+    verb: String,
+    start: usize,
+    end: usize,
+}
+
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    match line.find("//") {
+        Some(pos) => (&line[..pos], Some(&line[pos..])),
+        None => (line, None),
+    }
+}
+
+fn tokens(line: &str) -> Vec<String> {
+    line.split_whitespace()
+        .map(|x| x.trim_matches(['"', '`']).to_string())
+        .collect()
+}
+
+fn parse_gomod(text: &str) -> (Vec<Entry>, Vec<Block>) {
+    let mut entries = vec![];
+    let mut blocks: Vec<Block> = vec![];
+    let mut current: Option<usize> = None;
+    for (i, line) in text.lines().enumerate() {
+        let (code, comment) = split_comment(line);
+        let comment = comment.map(|x| x.to_string());
+        let args = tokens(code);
+        if let Some(block) = current {
+            if args.first().map(|x| x == ")").unwrap_or(false) {
+                blocks[block].end = i;
+                current = None;
+            } else if !args.is_empty() {
+                entries.push(Entry {
+                    verb: blocks[block].verb.to_string(),
+                    args,
+                    comment,
+                    line: i,
+                    block: Some(block),
+                });
+            }
+            continue;
+        }
+        match args.as_slice() {
+            [] => {}
+            [verb, open] if open == "(" => {
+                blocks.push(Block {
+                    verb: verb.to_string(),
+                    start: i,
+                    end: i,
+                });
+                current = Some(blocks.len() - 1);
+            }
+            [verb, rest @ ..] => entries.push(Entry {
+                verb: verb.to_string(),
+                args: rest.to_vec(),
+                comment,
+                line: i,
+                block: None,
+            }),
+        }
+    }
+    (entries, blocks)
+}
+
+fn read_gomod(folder: &str) -> Option<String> {
+    fs::read_to_string(format!("{}/go.mod", folder)).ok()
+}
+
+/// Parse a module version like `v1.2.3`, `v2.0.0+incompatible` or a
+/// pseudo-version like `v0.0.0-20200622213623-75b288015ac9`
+fn parse_version(version: &str) -> Option<Version> {
+    let semver = semver::Version::parse(version.strip_prefix('v')?).ok()?;
+    Some(Version::new(semver, version))
+}
+
+fn is_incompatible(version: &semver::Version) -> bool {
+    version
+        .build
+        .iter()
+        .any(|x| x.to_string() == "incompatible")
+}
+
+/// Split the major version suffix from a module path, `example.com/mod/v3`
+/// becoming `("example.com/mod", 3)`. Paths without a suffix are major
+/// version 0 or 1. Returns `None` for gopkg.in paths which carry the major
+/// version as part of the package name.
+fn split_major(path: &str) -> Option<(&str, u64)> {
+    if path.starts_with("gopkg.in/") {
+        return None;
+    }
+    if let Some(pos) = path.rfind('/') {
+        if let Some(major) = path[pos + 1..].strip_prefix('v') {
+            if let Ok(major) = major.parse::<u64>() {
+                if major >= 2 {
+                    return Some((&path[..pos], major));
+                }
+            }
+        }
+    }
+    Some((path, 1))
+}
+
+/// The module path to use for a version of the module, which changes with
+/// the major version
+fn path_for_version(path: &str, version: &semver::Version) -> String {
+    match split_major(path) {
+        Some((prefix, _)) if version.major >= 2 && !is_incompatible(version) => {
+            format!("{}/v{}", prefix, version.major)
+        }
+        Some((prefix, _)) => prefix.to_string(),
+        None => path.to_string(),
+    }
+}
+
+/// Minimal version selection picks at least the required version, and a
+/// module path only ever has a single major version. Excluded versions can
+/// never be selected.
+fn requirement(path: &str, version: &str, excluded: &[Version]) -> Option<VersionReq> {
+    let required = parse_version(version)?;
+    let mut comparators = vec![Comparator::new(Op::GreaterEq, (*required).clone())];
+    if !is_incompatible(&required) {
+        let major = split_major(path).map(|(_, m)| m).unwrap_or(required.major);
+        let upper = semver::Version::new(major.max(1) + 1, 0, 0);
+        comparators.push(Comparator::new(Op::Less, upper));
+    }
+    for version in excluded {
+        comparators.push(Comparator::new(Op::NotEqual, (**version).clone()));
+    }
+    Some(VersionReq::new(version, vec![comparators]))
+}
+
+/// Module replacements, keyed on the module path being replaced
+#[derive(Debug, Clone)]
+struct Replace {
+    /// Only this version is replaced if specified
+    version: Option<String>,
+    path: String,
+    /// No version for replacements with local directories
+    new_version: Option<String>,
+}
+
+fn replacements(entries: &[Entry]) -> HashMap<String, Vec<Replace>> {
+    let mut replaces: HashMap<String, Vec<Replace>> = HashMap::new();
+    for entry in entries.iter().filter(|x| x.verb == "replace") {
+        let arrow = match entry.args.iter().position(|x| x == "=>") {
+            Some(a) if a > 0 && a + 1 < entry.args.len() => a,
+            _ => continue,
+        };
+        replaces
+            .entry(entry.args[0].to_string())
+            .or_default()
+            .push(Replace {
+                version: entry.args.get(1).filter(|_| arrow == 2).cloned(),
+                path: entry.args[arrow + 1].to_string(),
+                new_version: entry.args.get(arrow + 2).cloned(),
+            });
+    }
+    replaces
+}
+
+/// The replacement in effect for a required version of a module, where one
+/// for that exact version wins over one for every version
+fn find_replace<'a>(
+    replaces: &'a HashMap<String, Vec<Replace>>,
+    path: &str,
+    required: &str,
+) -> Option<&'a Replace> {
+    replaces.get(path).and_then(|x| {
+        x.iter()
+            .find(|r| r.version.as_deref() == Some(required))
+            .or_else(|| x.iter().find(|r| r.version.is_none()))
+    })
+}
+
+/// The proxy to fetch module versions from. This follows the `GOPROXY`
+/// environment variable used by the go command, so it can also point to a
+/// local file server or a `file://` URL to work offline.
+fn proxy_url() -> String {
+    if env::var("MEAIN_TEST_ENV").is_ok() {
+        return "http://localhost:8000/goproxy".to_string();
+    }
+    env::var("GOPROXY")
+        .unwrap_or_default()
+        .split([',', '|'])
+        .map(|x| x.trim().trim_end_matches('/'))
+        .find(|x| !x.is_empty() && *x != "direct" && *x != "off")
+        .unwrap_or("https://proxy.golang.org")
+        .to_string()
+}
+
+/// Module paths are case-encoded on the proxy, uppercase letters being
+/// replaced by `!` followed by the lowercase letter
+fn escape_path(path: &str) -> String {
+    let mut escaped = String::new();
+    for c in path.chars() {
+        if c.is_ascii_uppercase() {
+            escaped.push('!');
+            escaped.push(c.to_ascii_lowercase());
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+async fn fetch_proxy(path: &str, file: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let url = format!("{}/{}/{}", proxy_url(), escape_path(path), file);
+    if let Some(local) = url.strip_prefix("file://") {
+        return Ok(fs::read_to_string(local).ok());
+    }
+    let resp = reqwest::Client::new()
+        .get(&url)
+        .header("User-Agent", "depman (github.com/meain/depman)")
+        .send()
+        .await?;
+    // The proxy uses 410 for modules it refuses to serve
+    if resp.status() == reqwest::StatusCode::NOT_FOUND || resp.status() == reqwest::StatusCode::GONE
+    {
+        return Ok(None);
+    }
+    Ok(Some(resp.error_for_status()?.text().await?))
+}
+
+/// All the versions of a module path, which only includes a single major
+/// version unless it has `+incompatible` versions
+async fn fetch_versions(path: &str) -> Result<Option<Vec<Version>>, Box<dyn std::error::Error>> {
+    let list = match fetch_proxy(path, "@v/list").await? {
+        Some(l) => l,
+        None => return Ok(None),
+    };
+    let mut versions: Vec<Version> = list
+        .lines()
+        .filter_map(|x| parse_version(x.trim()))
+        .collect();
+    // Modules without any tagged versions only have pseudo-versions
+    if versions.is_empty() {
+        if let Some(latest) = fetch_proxy(path, "@latest").await? {
+            let latest: LatestResponse = serde_json::from_str(&latest)?;
+            versions.extend(parse_version(&latest.version));
+        }
+    }
+    Ok(Some(versions))
+}
+
+fn add_require(text: &str, path: &str, version: &str, indirect: bool) -> String {
+    let (entries, blocks) = parse_gomod(text);
+    let comment = if indirect { " // indirect" } else { "" };
+    let mut lines: Vec<String> = text.lines().map(|x| x.to_string()).collect();
+
+    // Pick the block holding the same kind of requirements, as `go mod tidy`
+    // keeps direct and indirect dependencies in separate blocks
+    let requires = |block: usize| entries.iter().filter(move |x| x.block == Some(block));
+    let block = (0..blocks.len())
+        .filter(|b| blocks[*b].verb == "require" && blocks[*b].end > blocks[*b].start)
+        .find(|b| requires(*b).all(|x| x.is_indirect() == indirect))
+        .or_else(|| (0..blocks.len()).find(|b| blocks[*b].verb == "require"));
+
+    match block {
+        Some(b) => {
+            let position = requires(b)
+                .find(|x| x.args.first().map(|p| p.as_str() > path).unwrap_or(false))
+                .map(|x| x.line)
+                .unwrap_or(blocks[b].end);
+            lines.insert(position, format!("\t{} {}{}", path, version, comment));
+        }
+        None => {
+            if lines.last().map(|x| !x.is_empty()).unwrap_or(false) {
+                lines.push("".to_string());
+            }
+            lines.push(format!("require {} {}{}", path, version, comment));
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+pub struct GoModules;
+
+#[async_trait]
+impl Ecosystem for GoModules {
+    fn name(&self) -> &'static str {
+        "Go modules"
+    }
+
+    fn is_this_it(&self, folder: &str) -> bool {
+        read_gomod(folder).is_some()
+    }
+
+    fn parse_config(&self, folder: &str) -> Config {
+        let path_string = format!("{}/go.mod", folder);
+        let text = read_gomod(folder).unwrap_or_else(|| panic!("Unable to read {}", &path_string));
+        let (entries, _) = parse_gomod(&text);
+
+        let mut excluded: HashMap<&str, Vec<Version>> = HashMap::new();
+        for entry in entries.iter().filter(|x| x.verb == "exclude") {
+            if let [path, version] = entry.args.as_slice() {
+                if let Some(v) = parse_version(version) {
+                    excluded.entry(path).or_default().push(v);
+                }
+            }
+        }
+
+        let replaces = replacements(&entries);
+        let mut name = None;
+        let mut direct: DependencyGroup = BTreeMap::new();
+        let mut indirect: DependencyGroup = BTreeMap::new();
+        let mut direct_sources = HashMap::new();
+        let mut indirect_sources = HashMap::new();
+        for entry in &entries {
+            match (entry.verb.as_str(), entry.args.as_slice()) {
+                ("module", [path]) => name = Some(path.to_string()),
+                ("require", [path, version]) => {
+                    let exclusions = excluded.get(path.as_str()).map(|x| x.as_slice());
+                    let req = requirement(path, version, exclusions.unwrap_or(&[]));
+                    let (group, sources) = if entry.is_indirect() {
+                        (&mut indirect, &mut indirect_sources)
+                    } else {
+                        (&mut direct, &mut direct_sources)
+                    };
+                    group.insert(path.to_string(), req);
+                    // Local directories have no versions to fetch
+                    if let Some(r) = find_replace(&replaces, path, version) {
+                        if r.new_version.is_none() {
+                            let source = DependencySource::Path {
+                                package: path.to_string(),
+                                path: r.path.to_string(),
+                            };
+                            sources.insert(path.to_string(), source);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut groups = BTreeMap::new();
+        let mut sources = HashMap::new();
+        groups.insert("direct".to_string(), direct);
+        sources.insert("direct".to_string(), direct_sources);
+        if !indirect.is_empty() {
+            groups.insert("indirect".to_string(), indirect);
+            sources.insert("indirect".to_string(), indirect_sources);
+        }
+        Config {
+            name,
+            version: None,
+            groups,
+            sources,
+            features: HashMap::new(),
+            toolchain: None,
+        }
+    }
+
+    /// go.mod lists the selected version of every module in the build list,
+    /// go.sum tells us which of those have actually been downloaded
+    fn parse_lockfile(&self, folder: &str) -> Option<Lockfile> {
+        let text = read_gomod(folder)?;
+        let sums = fs::read_to_string(format!("{}/go.sum", folder)).ok()?;
+        let downloaded: HashSet<(&str, &str)> = sums
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let path = parts.next()?;
+                let version = parts.next()?.trim_end_matches("/go.mod");
+                Some((path, version))
+            })
+            .collect();
+
+        let (entries, _) = parse_gomod(&text);
+        let replaces = replacements(&entries);
+        let mut packages: Lockfile = HashMap::new();
+        for entry in entries.iter().filter(|x| x.verb == "require") {
+            let (path, required) = match entry.args.as_slice() {
+                [path, version] => (path, version),
+                _ => continue,
+            };
+            let replace = find_replace(&replaces, path, required);
+            // Versions of other modules or local directories are not
+            // versions of this module
+            let version = match replace {
+                Some(r) if &r.path == path => match &r.new_version {
+                    Some(v) => v,
+                    None => continue,
+                },
+                Some(_) => continue,
+                None => required,
+            };
+            if downloaded.contains(&(path.as_str(), version.as_str())) {
+                if let Some(v) = parse_version(version) {
+                    packages.entry(path.to_string()).or_default().push(v);
+                }
+            }
+        }
+        Some(packages)
+    }
+
+    /// Newer major versions live at a different module path, so those are
+    /// looked up as well to show them as breaking upgrades
+    async fn fetch_dep_info(&self, name: &str) -> Result<DepInfo, Box<dyn std::error::Error>> {
+        let mut versions = fetch_versions(name)
+            .await?
+            .ok_or_else(|| format!("{} not found on the module proxy", name))?;
+        if let Some((prefix, major)) = split_major(name) {
+            let mut next = major + 1;
+            while let Some(found) = fetch_versions(&format!("{}/v{}", prefix, next)).await? {
+                if found.is_empty() {
+                    break;
+                }
+                versions.extend(found);
+                next += 1;
+            }
+        }
+
+        let repository = match split_major(name) {
+            Some((prefix, _)) if prefix.starts_with("github.com/") => Some(format!(
+                "https://{}",
+                prefix.split('/').take(3).collect::<Vec<_>>().join("/")
+            )),
+            _ => None,
+        };
+        Ok(DepInfo {
+            name: name.to_string(),
+            author: None,
+            homepage: Some(format!("https://pkg.go.dev/{}", name)),
+            license: None,
+            description: None,
+            repository,
+            versions,
+            features: HashMap::new(),
+            withdrawn: HashMap::new(),
+            tags: HashMap::new(),
+            toolchain: HashMap::new(),
+            licenses: HashMap::new(),
+        })
+    }
+
+    fn delete_dep(
+        &self,
+        folder: &str,
+        _group: &str,
+        name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path_string = format!("{}/go.mod", folder);
+        let text = fs::read_to_string(&path_string)?;
+        let (entries, _) = parse_gomod(&text);
+        let remove: Vec<usize> = entries
+            .iter()
+            .filter(|x| x.verb == "require" && x.args.first() == Some(&name.to_string()))
+            .map(|x| x.line)
+            .collect();
+        let lines: Vec<&str> = text
+            .lines()
+            .enumerate()
+            .filter(|(i, _)| !remove.contains(i))
+            .map(|(_, line)| line)
+            .collect();
+        fs::write(&path_string, lines.join("\n") + "\n")?;
+        Ok(())
+    }
+
+    /// Moving to another major version changes the module path as well
+    fn install_dep(
+        &self,
+        dep: InstallCandidate,
+        folder: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path_string = format!("{}/go.mod", folder);
+        let mut text = fs::read_to_string(&path_string)?;
+        let version = if dep.version.starts_with('v') {
+            dep.version.to_string()
+        } else {
+            format!("v{}", dep.version)
+        };
+        let parsed = parse_version(&version).ok_or("Invalid module version")?;
+        let path = path_for_version(&dep.name, &parsed);
+
+        let (entries, _) = parse_gomod(&text);
+        let existing = entries
+            .iter()
+            .find(|x| x.verb == "require" && x.args.first() == Some(&dep.name));
+        match existing {
+            Some(entry) => {
+                let mut lines: Vec<String> = text.lines().map(|x| x.to_string()).collect();
+                let line = &lines[entry.line];
+                let indent = &line[..line.len() - line.trim_start().len()];
+                let verb = if entry.block.is_none() {
+                    "require "
+                } else {
+                    ""
+                };
+                let comment = match &entry.comment {
+                    Some(c) => format!(" {}", c),
+                    None => "".to_string(),
+                };
+                lines[entry.line] = format!("{}{}{} {}{}", indent, verb, path, version, comment);
+                text = lines.join("\n") + "\n";
+            }
+            None => text = add_require(&text, &path, &version, dep.kind == "indirect"),
+        }
+        fs::write(&path_string, text)?;
+        Ok(())
+    }
+
+    /// The module proxy does not have a search API, so all we can do is
+    /// to look up the search term as a module path
+    async fn search_dep(&self, term: &str) -> Result<Vec<SearchDep>, Box<dyn std::error::Error>> {
+        let latest = fetch_versions(term.trim())
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|x| x.pre.is_empty())
+            .max();
+        Ok(match latest {
+            Some(version) => vec![SearchDep {
+                name: term.trim().to_string(),
+                version: version.to_string(),
+            }],
+            None => vec![],
+        })
+    }
+}
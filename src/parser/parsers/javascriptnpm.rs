@@ -1,19 +1,27 @@
 use std::collections::hash_map::HashMap;
 use std::collections::BTreeMap;
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::BufReader;
 use std::path::Path;
+use std::time::SystemTime;
 
-use semver::{Version, VersionReq};
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use crate::{
-    parser::{Author, Config, DepInfo, DependencyGroup, Lockfile, SearchDep},
+    parser::{
+        advisory,
+        version::{split_operator, Comparator, Partial},
+        Advisory, Author, Config, DepInfo, DependencyGraph, DependencyGroup, DependencySource,
+        LockedPackage, Lockfile, SearchDep, Toolchain, Version, VersionReq, Withdrawal,
+    },
     render::InstallCandidate,
 };
 
+use super::jsonedit;
+use super::Ecosystem;
+
 /// For config file
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct JavascriptPackageJson {
@@ -22,16 +30,38 @@ struct JavascriptPackageJson {
     dependencies: Option<BTreeMap<String, String>>,
     #[serde(alias = "devDependencies")]
     dev_dependencies: Option<BTreeMap<String, String>>,
+    #[serde(alias = "packageManager")]
+    package_manager: Option<String>,
+    engines: Option<serde_json::Value>,
 }
 
 /// For lockfile
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct DepWithVersion {
     version: String,
+    /// Ranges of the packages this one depends on
+    #[serde(default)]
+    requires: BTreeMap<String, String>,
+    /// Copies nested inside this package in version 1 lockfiles
+    #[serde(default)]
+    dependencies: BTreeMap<String, DepWithVersion>,
+}
+/// Linked packages in the `packages` section do not have a version
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct LockfilePackage {
+    version: Option<String>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, String>,
+    #[serde(alias = "optionalDependencies", default)]
+    optional_dependencies: BTreeMap<String, String>,
 }
+/// Lockfile version 1 only has `dependencies`, version 2 has both and
+/// version 3 only has `packages`
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct JavascriptPackageJsonLockfile {
+    #[serde(default)]
     dependencies: BTreeMap<String, DepWithVersion>,
+    packages: Option<BTreeMap<String, LockfilePackage>>,
 }
 
 /// For metadata
@@ -42,7 +72,17 @@ pub struct NpmResponse {
     description: Option<String>,
     license: Option<String>,
     homepage: Option<String>,
-    versions: BTreeMap<String, DepWithVersion>,
+    versions: BTreeMap<String, NpmResponseVersion>,
+    #[serde(rename = "dist-tags", default)]
+    dist_tags: BTreeMap<String, String>,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct NpmResponseVersion {
+    /// Usually the deprecation message, but can be anything
+    deprecated: Option<serde_json::Value>,
+    engines: Option<serde_json::Value>,
+    /// An SPDX expression, or `{ "type": ... }` in older packages
+    license: Option<serde_json::Value>,
 }
 
 /// For search
@@ -60,13 +100,523 @@ struct NpmSearchResponse {
     objects: Vec<NpmSearchPackage>,
 }
 
+/// Parse the version spec of a dependency from package.json
+///
+/// Specs which do not refer to a version from the registry, like
+/// `file:../x`, `git+https://...`, `workspace:*` or dist-tags other than
+/// `latest`, are kept so that they can be displayed but do not match any
+/// version. Aliases like `npm:other@^1.0.0` use the range after the `@`.
+fn parse_spec(spec: &str) -> Option<VersionReq> {
+    let trimmed = spec.trim();
+    if trimmed == "latest" {
+        return Some(VersionReq::new(spec, vec![vec![]]));
+    }
+    let range = match trimmed.strip_prefix("npm:") {
+        Some(alias) => split_descriptor(alias)
+            .map(|(_, range)| range)
+            .unwrap_or(""),
+        None => trimmed,
+    };
+    let is_tag = range
+        .chars()
+        .next()
+        .map(|c| c.is_ascii_alphabetic() && !"xXv".contains(c))
+        .unwrap_or(false);
+    if range.contains([':', '/']) || is_tag {
+        return Some(VersionReq::new(spec, vec![]));
+    }
+    parse_range(range).map(|alternatives| VersionReq::new(spec, alternatives))
+}
+
+/// The package installed by an alias like `npm:other@^1.0.0`
+fn alias_source(spec: &str) -> Option<DependencySource> {
+    let alias = spec.trim().strip_prefix("npm:")?;
+    let package = split_descriptor(alias)
+        .map(|(name, _)| name)
+        .unwrap_or(alias);
+    if package.is_empty() {
+        return None;
+    }
+    Some(DependencySource::Registry {
+        package: package.to_string(),
+        registry: None,
+    })
+}
+
+/// Parse an npm range like `^1.2.3`, `1.2.x`, `>=1.0.0 <2.0.0`,
+/// `1.0.0 - 2.3.0` or `^1.0.0 || ^2.0.0` into its alternatives
+fn parse_range(range: &str) -> Option<Vec<Vec<Comparator>>> {
+    let mut alternatives = vec![];
+    for alternative in range.split("||") {
+        let words: Vec<&str> = alternative.split_whitespace().collect();
+        let mut comparators = vec![];
+        if let [lower, "-", upper] = words.as_slice() {
+            comparators.extend(npm_partial(lower)?.greater_eq());
+            comparators.extend(npm_partial(upper)?.less_eq());
+            alternatives.push(comparators);
+            continue;
+        }
+        // Operators can be separated from the version by a space
+        let mut pending = String::new();
+        for word in words {
+            pending.push_str(word);
+            if word.chars().all(|c| "<>=^~".contains(c)) {
+                continue;
+            }
+            comparators.extend(parse_comparator(&pending)?);
+            pending.clear();
+        }
+        alternatives.push(comparators);
+    }
+    Some(alternatives)
+}
+
+fn parse_comparator(comparator: &str) -> Option<Vec<Comparator>> {
+    let (op, version) = split_operator(comparator);
+    let partial = npm_partial(version)?;
+    Some(match op {
+        "" | "=" => partial.exact(),
+        "^" => partial.caret(),
+        "~" | "~>" => partial.tilde(),
+        ">" => partial.greater(),
+        ">=" => partial.greater_eq(),
+        "<" => partial.less(),
+        "<=" => partial.less_eq(),
+        _ => return None,
+    })
+}
+
+/// The range of node versions in an `engines` field, which old packages
+/// sometimes have in formats other than an object
+fn node_engine(engines: Option<&serde_json::Value>) -> Option<VersionReq> {
+    let range = engines?.get("node")?.as_str()?;
+    parse_range(range).map(|alternatives| VersionReq::new(range, alternatives))
+}
+
+fn npm_partial(version: &str) -> Option<Partial> {
+    let version = version.trim_start_matches(['v', '=']);
+    if version.is_empty() {
+        return Some(Partial::default());
+    }
+    Partial::parse(version)
+}
+
+/// The key in package.json for a dependency group
+fn package_json_key(group: &str) -> &str {
+    match group {
+        "dev-dependencies" => "devDependencies",
+        _ => group,
+    }
+}
+
+/// Prefix used for the range of newly added packages, following the
+/// `save-exact` and `save-prefix` settings in the .npmrc of the project
+fn save_prefix(folder: &str) -> String {
+    let text = fs::read_to_string(format!("{}/.npmrc", folder)).unwrap_or_default();
+    let mut prefix = "^".to_string();
+    let mut exact = false;
+    for line in text.lines().map(|x| x.trim()) {
+        if line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim().trim_matches(['"', '\'']);
+            match key.trim() {
+                "save-exact" => exact = value == "true",
+                "save-prefix" => prefix = value.to_string(),
+                _ => {}
+            }
+        }
+    }
+    if exact {
+        String::new()
+    } else {
+        prefix
+    }
+}
+
+/// The spec to write when upgrading to `version`. A single comparator
+/// keeps its operator and x-ranges like `1.x` or `1.2` keep their
+/// precision, anything else is replaced by `prefix` and the version.
+fn updated_spec(spec: &str, version: &str, prefix: &str) -> String {
+    let trimmed = spec.trim();
+    if let Some((name, range)) = trimmed.strip_prefix("npm:").and_then(split_descriptor) {
+        return format!("npm:{}@{}", name, updated_spec(range, version, prefix));
+    }
+    let (op, current) = split_operator(trimmed);
+    let current = current.trim_start_matches(['v', '=']);
+    let single = !trimmed.is_empty()
+        && !trimmed.contains(char::is_whitespace)
+        && Partial::parse(current).is_some();
+    if !single || !["", "=", "^", "~", ">="].contains(&op) {
+        return format!("{}{}", prefix, version);
+    }
+
+    let parts: Vec<&str> = current.split('.').collect();
+    let wildcard = |part: &&str| ["x", "X", "*"].contains(part);
+    if (parts.len() >= 3 && !wildcard(&parts[2])) || version.contains(['-', '+']) {
+        return format!("{}{}", op, version);
+    }
+    let release: Vec<&str> = version.split('.').collect();
+    let kept: Vec<&str> = parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            if wildcard(part) {
+                *part
+            } else {
+                *release.get(i).unwrap_or(part)
+            }
+        })
+        .collect();
+    format!("{}{}", op, kept.join("."))
+}
+
+/// Lockfiles of the different package managers, in the order in which
+/// they are picked if they are equally recent
+const LOCKFILES: [(&str, &str); 3] = [
+    ("npm", "package-lock.json"),
+    ("yarn", "yarn.lock"),
+    ("pnpm", "pnpm-lock.yaml"),
+];
+
+/// Find the lockfile to use. The package manager mentioned in the
+/// `packageManager` field of package.json wins, otherwise the most
+/// recently modified lockfile is the one which is in use.
+fn find_lockfile(folder: &str, package_json: &JavascriptPackageJson) -> Option<&'static str> {
+    let existing: Vec<(&str, &'static str)> = LOCKFILES
+        .iter()
+        .filter(|(_, file)| Path::new(&format!("{}/{}", folder, file)).exists())
+        .cloned()
+        .collect();
+    if let Some(manager) = &package_json.package_manager {
+        let manager = manager.split('@').next().unwrap_or("");
+        if let Some((_, file)) = existing.iter().find(|(name, _)| *name == manager) {
+            return Some(file);
+        }
+    }
+    let mut newest: Option<(SystemTime, &'static str)> = None;
+    for (_, file) in existing {
+        let modified = fs::metadata(format!("{}/{}", folder, file))
+            .and_then(|x| x.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        if newest.map(|(time, _)| modified > time).unwrap_or(true) {
+            newest = Some((modified, file));
+        }
+    }
+    newest.map(|(_, file)| file)
+}
+
+fn parse_npm_lockfile(path_string: &str) -> Option<Lockfile> {
+    let path = Path::new(&path_string);
+    let file = File::open(path).ok()?;
+    let reader = BufReader::new(file);
+    let parsed: JavascriptPackageJsonLockfile = serde_json::from_reader(reader)
+        .unwrap_or_else(|_| panic!("Unable to parse {}", &path_string));
+
+    let mut packages: Lockfile = HashMap::new();
+    if let Some(installed) = parsed.packages {
+        // The copy hoisted to the top level is the one the project uses, so
+        // it comes before the ones nested inside other packages
+        let mut nested = vec![];
+        for (path, package) in installed {
+            let version = match package.version.and_then(|x| Version::parse(&x).ok()) {
+                Some(v) => v,
+                None => continue,
+            };
+            match path.strip_prefix("node_modules/") {
+                Some(name) if !name.contains("/node_modules/") => {
+                    packages.entry(name.to_string()).or_default().push(version)
+                }
+                _ => {
+                    if let Some(pos) = path.rfind("node_modules/") {
+                        let name = &path[pos + "node_modules/".len()..];
+                        nested.push((name.to_string(), version));
+                    }
+                }
+            }
+        }
+        for (name, version) in nested {
+            packages.entry(name).or_default().push(version);
+        }
+        return Some(packages);
+    }
+    let mut pending: Vec<&BTreeMap<String, DepWithVersion>> = vec![&parsed.dependencies];
+    while !pending.is_empty() {
+        let mut next = vec![];
+        for deps in pending {
+            for (name, dep) in deps {
+                // or_else to deal with file:... like stuff
+                let version = Version::parse(&dep.version)
+                    .unwrap_or_else(|_| Version::parse("0.0.0").unwrap());
+                packages.entry(name.to_string()).or_default().push(version);
+                next.push(&dep.dependencies);
+            }
+        }
+        pending = next;
+    }
+    Some(packages)
+}
+
+/// Find the copy of `name` a package installed at `path` gets, which is
+/// the closest one going up through the node_modules folders
+fn resolve_installed(
+    installed: &BTreeMap<String, LockfilePackage>,
+    path: &str,
+    name: &str,
+) -> Option<LockedPackage> {
+    let mut base = path;
+    loop {
+        let candidate = if base.is_empty() {
+            format!("node_modules/{}", name)
+        } else {
+            format!("{}/node_modules/{}", base, name)
+        };
+        if let Some(package) = installed.get(&candidate) {
+            return Some(LockedPackage {
+                name: name.to_string(),
+                version: package.version.clone()?,
+            });
+        }
+        if base.is_empty() {
+            return None;
+        }
+        base = match base.rfind("/node_modules/") {
+            Some(pos) => &base[..pos],
+            None => "",
+        };
+    }
+}
+
+/// Add the packages nested in `deps` of a version 1 lockfile to the
+/// graph. Requirements are met by the copies nested inside the package
+/// itself, then the ones in the folders above it.
+fn add_nested_dependencies<'a>(
+    deps: &'a BTreeMap<String, DepWithVersion>,
+    scopes: &mut Vec<&'a BTreeMap<String, DepWithVersion>>,
+    graph: &mut DependencyGraph,
+) {
+    scopes.push(deps);
+    for (name, dep) in deps {
+        let dependencies = dep
+            .requires
+            .keys()
+            .filter_map(|x| {
+                let found = std::iter::once(&dep.dependencies)
+                    .chain(scopes.iter().rev().copied())
+                    .find_map(|scope| scope.get(x))?;
+                Some(LockedPackage {
+                    name: x.to_string(),
+                    version: found.version.to_string(),
+                })
+            })
+            .collect();
+        graph
+            .entry(LockedPackage {
+                name: name.to_string(),
+                version: dep.version.to_string(),
+            })
+            .or_insert(dependencies);
+        add_nested_dependencies(&dep.dependencies, scopes, graph);
+    }
+    scopes.pop();
+}
+
+fn parse_npm_dependency_graph(path_string: &str) -> Option<DependencyGraph> {
+    let file = File::open(Path::new(path_string)).ok()?;
+    let reader = BufReader::new(file);
+    let parsed: JavascriptPackageJsonLockfile = serde_json::from_reader(reader)
+        .unwrap_or_else(|_| panic!("Unable to parse {}", &path_string));
+
+    let mut graph: DependencyGraph = HashMap::new();
+    if let Some(installed) = &parsed.packages {
+        for (path, package) in installed {
+            let version = match &package.version {
+                Some(v) => v,
+                None => continue,
+            };
+            // The project itself and workspace members are not packages
+            let name = match path.rfind("node_modules/") {
+                Some(pos) => &path[pos + "node_modules/".len()..],
+                None => continue,
+            };
+            let dependencies = package
+                .dependencies
+                .keys()
+                .chain(package.optional_dependencies.keys())
+                .filter_map(|x| resolve_installed(installed, path, x))
+                .collect();
+            graph
+                .entry(LockedPackage {
+                    name: name.to_string(),
+                    version: version.to_string(),
+                })
+                .or_insert(dependencies);
+        }
+        return Some(graph);
+    }
+    add_nested_dependencies(&parsed.dependencies, &mut vec![], &mut graph);
+    Some(graph)
+}
+
+/// Split a descriptor like `@babel/core@^7.0.0` into the name and range
+fn split_descriptor(descriptor: &str) -> Option<(&str, &str)> {
+    let pos = descriptor.get(1..)?.find('@')? + 1;
+    Some((&descriptor[..pos], &descriptor[pos + 1..]))
+}
+
+/// Read the version resolved for each of the ranges in package.json from
+/// a yarn.lock file, followed by the rest of the versions in it. Both the
+/// custom format used by Yarn v1 and the YAML used by Yarn Berry have
+/// entries like this:
+///
+/// ```text
+/// "@babel/core@^7.0.0", "@babel/core@^7.1.0":
+///   version "7.12.3"
+/// ```
+///
+/// Berry prefixes the ranges with the protocol, as in `npm:^7.0.0`, and
+/// uses `version: 7.12.3` instead.
+fn parse_yarn_lockfile(text: &str, ranges: &BTreeMap<String, String>) -> Lockfile {
+    let mut resolved: HashMap<(String, String), Version> = HashMap::new();
+    let mut descriptors: Vec<(String, String)> = vec![];
+    for line in text.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        if !line.starts_with(' ') {
+            descriptors = line
+                .trim_end_matches(':')
+                .split(", ")
+                .filter_map(|x| split_descriptor(x.trim_matches('"')))
+                .map(|(name, range)| {
+                    let range = range.strip_prefix("npm:").unwrap_or(range);
+                    (name.to_string(), range.to_string())
+                })
+                .collect();
+            continue;
+        }
+        let trimmed = line.trim();
+        // Only the version of the entry itself and not of its dependencies
+        if line.len() - trimmed.len() != 2 {
+            continue;
+        }
+        let version = match trimmed.strip_prefix("version") {
+            Some(v) => v.trim_start_matches(':').trim().trim_matches('"'),
+            None => continue,
+        };
+        if let Ok(version) = Version::parse(version) {
+            for descriptor in descriptors.drain(..) {
+                resolved.insert(descriptor, version.clone());
+            }
+        }
+    }
+
+    // The versions the ranges in package.json resolved to come first
+    let mut packages: Lockfile = HashMap::new();
+    for (name, range) in ranges {
+        let range = range.strip_prefix("npm:").unwrap_or(range);
+        if let Some(version) = resolved.get(&(name.to_string(), range.to_string())) {
+            packages
+                .entry(name.to_string())
+                .or_default()
+                .push(version.clone());
+        }
+    }
+    let mut others: Vec<(String, Version)> = resolved
+        .into_iter()
+        .map(|((name, _), version)| (name, version))
+        .collect();
+    others.sort();
+    for (name, version) in others {
+        packages.entry(name).or_default().push(version);
+    }
+    packages
+}
+
+/// Read the versions of the direct dependencies from pnpm-lock.yaml,
+/// followed by the rest of the versions in it
+///
+/// Older lockfiles have the version as the value while newer ones have
+/// the specifier and version in a mapping. Projects in a workspace are
+/// listed under `importers`. Versions can have the versions of peer
+/// dependencies after them like `1.0.0_react@17.0.2` or
+/// `1.0.0(react@17.0.2)`.
+fn parse_pnpm_lockfile(text: &str) -> Lockfile {
+    let mut packages: Lockfile = HashMap::new();
+    let parsed: serde_yaml::Value = match serde_yaml::from_str(text) {
+        Ok(p) => p,
+        Err(_) => return packages,
+    };
+    let root = match parsed.get("importers").and_then(|x| x.get(".")) {
+        Some(importer) => importer,
+        None => &parsed,
+    };
+    for group in &["dependencies", "devDependencies", "optionalDependencies"] {
+        let deps = match root.get(group).and_then(|x| x.as_mapping()) {
+            Some(d) => d,
+            None => continue,
+        };
+        for (name, dep) in deps {
+            let version = match dep {
+                serde_yaml::Value::Mapping(_) => dep.get("version").and_then(|x| x.as_str()),
+                _ => dep.as_str(),
+            };
+            let version = version
+                .and_then(|x| x.split(['_', '(']).next())
+                .and_then(|x| Version::parse(x).ok());
+            if let (Some(name), Some(version)) = (name.as_str(), version) {
+                packages.entry(name.to_string()).or_default().push(version);
+            }
+        }
+    }
+
+    // Every version which is installed, with keys like `/name/1.0.0` in
+    // older lockfiles and `/name@1.0.0` or `name@1.0.0` in newer ones
+    let installed = parsed.get("packages").and_then(|x| x.as_mapping());
+    let mut others = vec![];
+    for key in installed
+        .into_iter()
+        .flat_map(|x| x.iter().map(|(key, _)| key))
+    {
+        let key = match key.as_str() {
+            Some(k) => k.trim_start_matches('/').split('(').next().unwrap_or(""),
+            None => continue,
+        };
+        let (name, version) = match key.rsplit_once('/') {
+            Some((name, version)) if version.starts_with(|c: char| c.is_ascii_digit()) => {
+                (name, version)
+            }
+            _ => match split_descriptor(key) {
+                Some(parts) => parts,
+                None => continue,
+            },
+        };
+        let version = version.split('_').next().unwrap_or("");
+        if let Ok(version) = Version::parse(version) {
+            others.push((name.to_string(), version));
+        }
+    }
+    others.sort();
+    for (name, version) in others {
+        packages.entry(name).or_default().push(version);
+    }
+    packages
+}
+
 pub struct JavascriptNpm;
-impl JavascriptNpm {
-    pub fn is_this_it(folder: &str) -> bool {
+
+#[async_trait]
+impl Ecosystem for JavascriptNpm {
+    fn name(&self) -> &'static str {
+        "npm"
+    }
+
+    fn is_this_it(&self, folder: &str) -> bool {
         Path::new(&format!("{}/package.json", folder)).exists()
     }
 
-    pub fn parse_config(folder: &str) -> Config {
+    fn parse_config(&self, folder: &str) -> Config {
         let path_string = format!("{}/package.json", folder);
         let path = Path::new(&path_string);
         let file = File::open(path).unwrap_or_else(|_| panic!("Unable to read {}", &path_string));
@@ -75,25 +625,26 @@ impl JavascriptNpm {
             .unwrap_or_else(|_| panic!("Unable to parse {}", &path_string));
 
         let mut groups: BTreeMap<String, DependencyGroup> = BTreeMap::new();
-        if let Some(grp) = parsed.dependencies {
-            let mut group: BTreeMap<String, Option<VersionReq>> = BTreeMap::new();
-            for dep in grp.keys() {
-                group.insert(
-                    dep.to_string(),
-                    VersionReq::parse(grp.get(dep).unwrap()).ok(),
-                );
-            }
-            groups.insert("dependencies".to_string(), group);
-        }
-        if let Some(grp) = parsed.dev_dependencies {
+        let mut sources = HashMap::new();
+        let found = vec![
+            ("dependencies", parsed.dependencies),
+            ("dev-dependencies", parsed.dev_dependencies),
+        ];
+        for (kind, grp) in found {
+            let grp = match grp {
+                Some(g) => g,
+                None => continue,
+            };
             let mut group: BTreeMap<String, Option<VersionReq>> = BTreeMap::new();
-            for dep in grp.keys() {
-                group.insert(
-                    dep.to_string(),
-                    VersionReq::parse(grp.get(dep).unwrap()).ok(),
-                );
+            let mut aliases = HashMap::new();
+            for (dep, spec) in &grp {
+                group.insert(dep.to_string(), parse_spec(spec));
+                if let Some(source) = alias_source(spec) {
+                    aliases.insert(dep.to_string(), source);
+                }
             }
-            groups.insert("dev-dependencies".to_string(), group);
+            groups.insert(kind.to_string(), group);
+            sources.insert(kind.to_string(), aliases);
         }
 
         let mut version = None;
@@ -101,34 +652,71 @@ impl JavascriptNpm {
             version = Version::parse(&v).ok()
         }
 
+        let toolchain = node_engine(parsed.engines.as_ref())
+            .and_then(|x| x.lower_bound())
+            .map(|x| Toolchain {
+                name: "node".to_string(),
+                version: Version::new(x.clone(), &x.to_string()),
+            });
+
         Config {
             name: parsed.name,
             version,
             groups,
+            sources,
+            features: HashMap::new(),
+            toolchain,
         }
     }
 
-    pub fn parse_lockfile(folder: &str) -> Option<Lockfile> {
-        let path_string = format!("{}/package-lock.json", folder);
-        let path = Path::new(&path_string);
-        let file = File::open(path).ok()?;
-        let reader = BufReader::new(file);
-        let parsed: JavascriptPackageJsonLockfile = serde_json::from_reader(reader)
+    fn parse_lockfile(&self, folder: &str) -> Option<Lockfile> {
+        let path_string = format!("{}/package.json", folder);
+        let text = fs::read_to_string(&path_string).ok()?;
+        let package_json: JavascriptPackageJson = serde_json::from_str(&text)
             .unwrap_or_else(|_| panic!("Unable to parse {}", &path_string));
 
-        let mut packages: Lockfile = HashMap::new();
-        for dep in parsed.dependencies.keys() {
-            packages.insert(
-                dep.to_string(),
-                Version::parse(&parsed.dependencies.get(dep).unwrap().version)
-                    .unwrap_or_else(|_| Version::parse("0.0.0").unwrap()), // or_else to deal with file:... like stuff
-            );
+        let lockfile = find_lockfile(folder, &package_json)?;
+        let path_string = format!("{}/{}", folder, lockfile);
+        match lockfile {
+            "yarn.lock" => {
+                let mut ranges = package_json.dependencies.unwrap_or_default();
+                ranges.extend(package_json.dev_dependencies.unwrap_or_default());
+                Some(parse_yarn_lockfile(
+                    &fs::read_to_string(path_string).ok()?,
+                    &ranges,
+                ))
+            }
+            "pnpm-lock.yaml" => Some(parse_pnpm_lockfile(&fs::read_to_string(path_string).ok()?)),
+            _ => parse_npm_lockfile(&path_string),
+        }
+    }
+
+    fn parse_dependency_graph(&self, folder: &str) -> Option<DependencyGraph> {
+        let path_string = format!("{}/package.json", folder);
+        let text = fs::read_to_string(&path_string).ok()?;
+        let package_json: JavascriptPackageJson = serde_json::from_str(&text)
+            .unwrap_or_else(|_| panic!("Unable to parse {}", &path_string));
+
+        // Only the lockfile of npm is read for now
+        match find_lockfile(folder, &package_json)? {
+            "package-lock.json" => {
+                parse_npm_dependency_graph(&format!("{}/package-lock.json", folder))
+            }
+            _ => None,
+        }
+    }
+
+    /// Advisories come from a checkout of the GitHub advisory database,
+    /// pointed at by `DEPMAN_GHSA_DB`
+    fn load_advisories(&self) -> Vec<Advisory> {
+        match env::var("DEPMAN_GHSA_DB") {
+            Ok(folder) => advisory::read_osv(Path::new(&folder), "npm"),
+            Err(_) => vec![],
         }
-        Some(packages)
     }
 
     #[allow(clippy::useless_let_if_seq)]
-    pub async fn fetch_dep_info(name: &str) -> Result<DepInfo, Box<dyn std::error::Error>> {
+    async fn fetch_dep_info(&self, name: &str) -> Result<DepInfo, Box<dyn std::error::Error>> {
         let mut url = format!("https://registry.npmjs.org/{}", name);
         if env::var("MEAIN_TEST_ENV").is_ok() {
             url = format!("http://localhost:8000/npm/{}.json", name);
@@ -140,6 +728,42 @@ impl JavascriptNpm {
             .keys()
             .map(|x| Version::parse(&x).unwrap())
             .collect();
+        let withdrawn = resp
+            .versions
+            .iter()
+            .filter_map(|(version, info)| {
+                // Deprecating with an empty message undoes a deprecation
+                let message = match info.deprecated.as_ref()? {
+                    serde_json::Value::String(m) if !m.is_empty() => m.to_string(),
+                    serde_json::Value::Bool(true) => "".to_string(),
+                    _ => return None,
+                };
+                Some((version.to_string(), Withdrawal::Deprecated(message)))
+            })
+            .collect();
+        let toolchain = resp
+            .versions
+            .iter()
+            .filter_map(|(version, info)| {
+                Some((version.to_string(), node_engine(info.engines.as_ref())?))
+            })
+            .collect();
+        let licenses = resp
+            .versions
+            .iter()
+            .filter_map(|(version, info)| {
+                let license = match info.license.as_ref()? {
+                    serde_json::Value::String(l) => l.to_string(),
+                    serde_json::Value::Object(o) => o.get("type")?.as_str()?.to_string(),
+                    _ => return None,
+                };
+                Some((version.to_string(), license))
+            })
+            .collect();
+        let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+        for (tag, version) in resp.dist_tags {
+            tags.entry(version).or_default().push(tag);
+        }
 
         Ok(DepInfo {
             name: name.to_string(),
@@ -149,38 +773,45 @@ impl JavascriptNpm {
             description: resp.description,
             repository: Some(format!("https://www.npmjs.com/package/{}", name)),
             versions,
+            features: HashMap::new(),
+            withdrawn,
+            tags,
+            toolchain,
+            licenses,
         })
     }
 
-    pub fn delete_dep(
+    fn delete_dep(
+        &self,
         folder: &str,
         group: &str,
         name: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let path_string = format!("{}/package.json", folder);
         let data = std::fs::read_to_string(&path_string)?;
-        let mut package_json: serde_json::Value = serde_json::from_str(&data)?;
-        if let Value::Object(pj) = &mut package_json[group] {
-            pj.remove(name);
-        }
-        std::fs::write(&path_string, serde_json::to_string_pretty(&package_json)?)?;
+        let key = package_json_key(group);
+        std::fs::write(&path_string, jsonedit::remove(&data, &[key], name)?)?;
         Ok(())
     }
 
-    pub fn install_dep(
+    fn install_dep(
+        &self,
         dep: InstallCandidate,
         folder: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let path_string = format!("{}/package.json", folder);
         let data = std::fs::read_to_string(&path_string)?;
-        let mut package_json: serde_json::Value = serde_json::from_str(&data)?;
-        package_json[dep.kind][dep.name] =
-            serde_json::Value::String("^".to_string() + &dep.version);
-        std::fs::write(&path_string, serde_json::to_string_pretty(&package_json)?)?;
+        let key = package_json_key(&dep.kind);
+        let version = match jsonedit::get_string(&data, &[key], &dep.name) {
+            Some(current) => updated_spec(&current, &dep.version, &save_prefix(folder)),
+            None => format!("{}{}", save_prefix(folder), dep.version),
+        };
+        let edited = jsonedit::set_string(&data, &[key], &dep.name, &version)?;
+        std::fs::write(&path_string, edited)?;
         Ok(())
     }
 
-    pub async fn search_dep(name: &str) -> Result<Vec<SearchDep>, Box<dyn std::error::Error>> {
+    async fn search_dep(&self, name: &str) -> Result<Vec<SearchDep>, Box<dyn std::error::Error>> {
         let url = format!(
             "http://registry.npmjs.com/-/v1/search?text={}&size=20",
             name
@@ -0,0 +1,366 @@
+//! Editing JSON files in place
+//!
+//! Going through `serde_json` to edit a file throws away the formatting,
+//! which makes for noisy diffs. The functions in here only touch the text
+//! of the member being edited and use the indentation already used in the
+//! file for anything new, much like `toml_edit` does for TOML.
+
+use std::error::Error;
+
+/// A `"key": value` pair in an object, with the positions of the start of
+/// the key and the value and the end of the value
+#[derive(Debug, Clone)]
+struct Member {
+    key: String,
+    key_start: usize,
+    value_start: usize,
+    value_end: usize,
+}
+
+fn invalid(position: usize) -> Box<dyn Error> {
+    format!("Invalid JSON at position {}", position).into()
+}
+
+fn skip_whitespace(text: &str, mut i: usize) -> usize {
+    let bytes = text.as_bytes();
+    while i < bytes.len() && (bytes[i] as char).is_ascii_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+fn skip_string(text: &str, i: usize) -> Result<usize, Box<dyn Error>> {
+    let bytes = text.as_bytes();
+    let mut j = i + 1;
+    while j < bytes.len() {
+        match bytes[j] {
+            b'\\' => j += 2,
+            b'"' => return Ok(j + 1),
+            _ => j += 1,
+        }
+    }
+    Err(invalid(i))
+}
+
+/// Find the end of the value starting at `i`
+fn skip_value(text: &str, i: usize) -> Result<usize, Box<dyn Error>> {
+    let bytes = text.as_bytes();
+    match bytes.get(i) {
+        Some(b'"') => skip_string(text, i),
+        Some(b'{') | Some(b'[') => {
+            let mut depth = 0;
+            let mut j = i;
+            while j < bytes.len() {
+                match bytes[j] {
+                    b'"' => {
+                        j = skip_string(text, j)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Ok(j + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                j += 1;
+            }
+            Err(invalid(i))
+        }
+        Some(_) => {
+            let end = text[i..]
+                .find(|c: char| c == ',' || c == '}' || c == ']' || c.is_ascii_whitespace())
+                .map(|x| x + i)
+                .unwrap_or(text.len());
+            Ok(end)
+        }
+        None => Err(invalid(i)),
+    }
+}
+
+/// The members of the object starting at `start` along with the position
+/// of its closing brace
+fn members(text: &str, start: usize) -> Result<(Vec<Member>, usize), Box<dyn Error>> {
+    let bytes = text.as_bytes();
+    if bytes.get(start) != Some(&b'{') {
+        return Err(invalid(start));
+    }
+    let mut members = vec![];
+    let mut i = skip_whitespace(text, start + 1);
+    loop {
+        match bytes.get(i) {
+            Some(b'}') => return Ok((members, i)),
+            Some(b',') => i = skip_whitespace(text, i + 1),
+            Some(b'"') => {
+                let key_end = skip_string(text, i)?;
+                let key: String = serde_json::from_str(&text[i..key_end])?;
+                let colon = skip_whitespace(text, key_end);
+                if bytes.get(colon) != Some(&b':') {
+                    return Err(invalid(colon));
+                }
+                let value_start = skip_whitespace(text, colon + 1);
+                let value_end = skip_value(text, value_start)?;
+                members.push(Member {
+                    key,
+                    key_start: i,
+                    value_start,
+                    value_end,
+                });
+                i = skip_whitespace(text, value_end);
+            }
+            _ => return Err(invalid(i)),
+        }
+    }
+}
+
+/// Position of the opening brace of the object at `path`
+fn find_object(text: &str, path: &[&str]) -> Result<Option<usize>, Box<dyn Error>> {
+    let mut start = skip_whitespace(text, 0);
+    for key in path {
+        let (members, _) = members(text, start)?;
+        match members.iter().find(|x| &x.key == key) {
+            Some(member) if text.as_bytes()[member.value_start] == b'{' => {
+                start = member.value_start
+            }
+            _ => return Ok(None),
+        }
+    }
+    Ok(Some(start))
+}
+
+/// The whitespace at the start of the line containing position `i`, or
+/// `None` if there is something other than whitespace before `i`
+fn line_indent(text: &str, i: usize) -> Option<&str> {
+    let line_start = text[..i].rfind('\n').map(|x| x + 1).unwrap_or(0);
+    let prefix = &text[line_start..i];
+    if prefix.chars().all(|c| c == ' ' || c == '\t') {
+        Some(prefix)
+    } else {
+        None
+    }
+}
+
+/// The indentation used for a single level in the file
+fn indent_unit(text: &str) -> String {
+    for line in text.lines() {
+        let indent: String = line
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        if indent.starts_with('\t') {
+            return "\t".to_string();
+        }
+        if !indent.is_empty() && !line.trim().is_empty() {
+            return indent;
+        }
+    }
+    "  ".to_string()
+}
+
+/// Add `"key": value` to the end of the object starting at `start`, where
+/// `value` is given the indentation of the members of the object
+fn insert_member(
+    text: &str,
+    start: usize,
+    key: &str,
+    value: &str,
+) -> Result<String, Box<dyn Error>> {
+    let (members, close) = members(text, start)?;
+    let key = serde_json::to_string(key)?;
+    let unit = indent_unit(text);
+    let line_start = text[..start].rfind('\n').map(|x| x + 1).unwrap_or(0);
+    let outer: String = text[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect();
+
+    let mut out = String::new();
+    match members.last() {
+        Some(last) => {
+            let separator = match line_indent(text, last.key_start) {
+                Some(indent) => format!(",\n{}", indent),
+                None => ", ".to_string(),
+            };
+            let indent = line_indent(text, last.key_start).unwrap_or("");
+            out.push_str(&text[..last.value_end]);
+            out.push_str(&separator);
+            out.push_str(&format!("{}: {}", key, reindent(value, indent)));
+            out.push_str(&text[last.value_end..]);
+        }
+        None => {
+            let indent = format!("{}{}", outer, unit);
+            out.push_str(&text[..=start]);
+            out.push_str(&format!(
+                "\n{}{}: {}\n{}",
+                indent,
+                key,
+                reindent(value, &indent),
+                outer
+            ));
+            out.push_str(&text[close..]);
+        }
+    }
+    Ok(out)
+}
+
+fn reindent(value: &str, indent: &str) -> String {
+    value.replace('\n', &format!("\n{}", indent))
+}
+
+/// Set the string value of `key` in the object at `path`, which has to
+/// exist already
+pub fn set_string(
+    text: &str,
+    path: &[&str],
+    key: &str,
+    value: &str,
+) -> Result<String, Box<dyn Error>> {
+    let encoded = serde_json::to_string(value)?;
+    let start = find_object(text, path)?
+        .ok_or_else(|| format!("No {} object in the file", path.join(".")))?;
+    let (members, _) = members(text, start)?;
+    match members.iter().find(|x| x.key == key) {
+        Some(member) => Ok(format!(
+            "{}{}{}",
+            &text[..member.value_start],
+            encoded,
+            &text[member.value_end..]
+        )),
+        None => insert_member(text, start, key, &encoded),
+    }
+}
+
+/// The string value of `key` in the object at `path`
+pub fn get_string(text: &str, path: &[&str], key: &str) -> Option<String> {
+    let start = find_object(text, path).ok()??;
+    let (members, _) = members(text, start).ok()?;
+    let member = members.into_iter().find(|x| x.key == key)?;
+    serde_json::from_str(&text[member.value_start..member.value_end]).ok()
+}
+
+/// Remove `key` from the object at `path` along with the separator before
+/// or after it
+pub fn remove(text: &str, path: &[&str], key: &str) -> Result<String, Box<dyn Error>> {
+    let start = match find_object(text, path)? {
+        Some(s) => s,
+        None => return Ok(text.to_string()),
+    };
+    let (members, close) = members(text, start)?;
+    let position = match members.iter().position(|x| x.key == key) {
+        Some(p) => p,
+        None => return Ok(text.to_string()),
+    };
+    let (from, to) = if let Some(next) = members.get(position + 1) {
+        (members[position].key_start, next.key_start)
+    } else if position > 0 {
+        (members[position - 1].value_end, members[position].value_end)
+    } else {
+        (start + 1, close)
+    };
+    Ok(format!("{}{}", &text[..from], &text[to..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKAGE: &str = r#"{
+  "name": "app",
+  "dependencies": {
+    "left-pad": "^1.0.0",
+    "react": "^16.0.0"
+  }
+}
+"#;
+
+    #[test]
+    fn set_string_only_touches_the_value() {
+        let edited = set_string(PACKAGE, &["dependencies"], "react", "^17.0.0").unwrap();
+        assert_eq!(edited, PACKAGE.replace("^16.0.0", "^17.0.0"));
+        let back = set_string(&edited, &["dependencies"], "react", "^16.0.0").unwrap();
+        assert_eq!(back, PACKAGE);
+    }
+
+    #[test]
+    fn insert_uses_the_indentation_of_the_members() {
+        let edited = set_string(PACKAGE, &["dependencies"], "vue", "^3.0.0").unwrap();
+        assert_eq!(
+            edited,
+            PACKAGE.replace("\"^16.0.0\"\n", "\"^16.0.0\",\n    \"vue\": \"^3.0.0\"\n")
+        );
+    }
+
+    #[test]
+    fn insert_with_tabs() {
+        let text = "{\n\t\"dependencies\": {\n\t\t\"react\": \"^16.0.0\"\n\t}\n}\n";
+        let edited = set_string(text, &["dependencies"], "vue", "^3.0.0").unwrap();
+        assert_eq!(
+            edited,
+            "{\n\t\"dependencies\": {\n\t\t\"react\": \"^16.0.0\",\n\t\t\"vue\": \"^3.0.0\"\n\t}\n}\n"
+        );
+    }
+
+    #[test]
+    fn insert_into_single_line_object() {
+        let text = "{\n  \"dependencies\": { \"react\": \"^16.0.0\" }\n}\n";
+        let edited = set_string(text, &["dependencies"], "vue", "^3.0.0").unwrap();
+        assert_eq!(
+            edited,
+            "{\n  \"dependencies\": { \"react\": \"^16.0.0\", \"vue\": \"^3.0.0\" }\n}\n"
+        );
+    }
+
+    #[test]
+    fn insert_into_empty_object() {
+        let text = "{\n  \"dependencies\": {}\n}\n";
+        let edited = set_string(text, &["dependencies"], "vue", "^3.0.0").unwrap();
+        assert_eq!(
+            edited,
+            "{\n  \"dependencies\": {\n    \"vue\": \"^3.0.0\"\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn missing_object_is_an_error() {
+        assert!(set_string(PACKAGE, &["devDependencies"], "vue", "^3.0.0").is_err());
+        assert_eq!(
+            remove(PACKAGE, &["devDependencies"], "vue").unwrap(),
+            PACKAGE
+        );
+    }
+
+    #[test]
+    fn strings_with_braces_and_quotes() {
+        let text = r#"{"scripts": {"x": "echo \"}\" {"}, "dependencies": {"a": "1"}}"#;
+        assert_eq!(
+            get_string(text, &["scripts"], "x").unwrap(),
+            r#"echo "}" {"#
+        );
+        let edited = set_string(text, &["dependencies"], "a", "2").unwrap();
+        assert_eq!(edited, text.replace(r#""a": "1""#, r#""a": "2""#));
+    }
+
+    #[test]
+    fn bare_literals_are_skipped() {
+        let text = r#"{"private": true, "count": 12, "none": null, "dependencies": {"a": "1"}}"#;
+        assert_eq!(get_string(text, &["dependencies"], "a").unwrap(), "1");
+        assert_eq!(get_string(text, &[], "count"), None);
+        let edited = set_string(text, &[], "none", "x").unwrap();
+        assert_eq!(edited, text.replace("null", "\"x\""));
+    }
+
+    #[test]
+    fn remove_first_last_and_only_member() {
+        let first = remove(PACKAGE, &["dependencies"], "left-pad").unwrap();
+        assert_eq!(
+            first,
+            PACKAGE.replace("\"left-pad\": \"^1.0.0\",\n    ", "")
+        );
+        let last = remove(PACKAGE, &["dependencies"], "react").unwrap();
+        assert_eq!(last, PACKAGE.replace(",\n    \"react\": \"^16.0.0\"", ""));
+        let only = remove(&first, &["dependencies"], "react").unwrap();
+        assert_eq!(only, "{\n  \"name\": \"app\",\n  \"dependencies\": {}\n}\n");
+    }
+}
@@ -1,68 +1,103 @@
+mod gomodules;
 mod javascriptnpm;
+mod jsonedit;
+mod phpcomposer;
+mod python;
+mod pythonpip;
+mod pythonpoetry;
+mod rubybundler;
 mod rustcargo;
 
-use super::{Config, Lockfile};
-use super::{DepInfo, ParserKind, SearchDep};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use super::{Advisory, Config, DependencyGraph, Lockfile};
+use super::{DepInfo, DependencyFeatures, SearchDep};
 
 use crate::render::InstallCandidate;
+use gomodules::GoModules;
 use javascriptnpm::JavascriptNpm;
+use phpcomposer::PhpComposer;
+use pythonpip::PythonPip;
+use pythonpoetry::PythonPoetry;
+use rubybundler::RubyBundler;
 use rustcargo::RustCargo;
 
-pub fn is_this_it(folder: &str, kind: &ParserKind) -> bool {
-    match kind {
-        ParserKind::RustCargo => RustCargo::is_this_it(folder),
-        ParserKind::JavascriptNpm => JavascriptNpm::is_this_it(folder),
-    }
-}
+/// A package manager that depman knows how to read and edit
+///
+/// Adding support for a new package manager is a matter of implementing
+/// this trait and adding the implementation to `ecosystems`.
+#[async_trait]
+pub trait Ecosystem: Send + Sync {
+    /// Human readable name of the package manager
+    fn name(&self) -> &'static str;
 
-pub fn parse_config(folder: &str, kind: &ParserKind) -> Config {
-    match kind {
-        ParserKind::RustCargo => RustCargo::parse_config(folder),
-        ParserKind::JavascriptNpm => JavascriptNpm::parse_config(folder),
-    }
-}
+    /// Check if the project in `folder` is managed by this package manager
+    fn is_this_it(&self, folder: &str) -> bool;
+
+    fn parse_config(&self, folder: &str) -> Config;
+
+    fn parse_lockfile(&self, folder: &str) -> Option<Lockfile>;
 
-pub fn parse_lockfile(folder: &str, kind: &ParserKind) -> Lockfile {
-    let lockfile = match kind {
-        ParserKind::RustCargo => RustCargo::parse_lockfile(folder),
-        ParserKind::JavascriptNpm => JavascriptNpm::parse_lockfile(folder),
-    };
-    match lockfile {
-        Some(lf) => lf,
-        None => Lockfile::new()
+    async fn fetch_dep_info(&self, name: &str) -> Result<DepInfo, Box<dyn std::error::Error>>;
+
+    fn delete_dep(
+        &self,
+        folder: &str,
+        group: &str,
+        name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    fn install_dep(
+        &self,
+        dep: InstallCandidate,
+        folder: &str,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    async fn search_dep(&self, term: &str) -> Result<Vec<SearchDep>, Box<dyn std::error::Error>>;
+
+    /// Change the features enabled for a dependency. Only some package
+    /// managers have features, so this fails for the rest.
+    fn update_features(
+        &self,
+        _folder: &str,
+        _group: &str,
+        _name: &str,
+        _features: &DependencyFeatures,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Err(format!("{} does not support features", self.name()).into())
     }
-}
 
-pub async fn fetch_dep_info(
-    name: String,
-    kind: &ParserKind,
-) -> Result<DepInfo, Box<dyn std::error::Error>> {
-    match kind {
-        ParserKind::RustCargo => RustCargo::fetch_dep_info(&name).await,
-        ParserKind::JavascriptNpm => JavascriptNpm::fetch_dep_info(&name).await,
+    /// Anything left for the user to do once a dependency is installed into
+    /// `group`, like regenerating a file which is derived from it
+    fn install_notice(&self, _folder: &str, _group: &str) -> Option<String> {
+        None
     }
-}
 
-pub fn delete_dep(kind: &ParserKind, folder: &str, group: &str, name: &str) -> bool {
-    match kind {
-        ParserKind::RustCargo => RustCargo::delete_dep(folder, group, name).is_ok(),
-        ParserKind::JavascriptNpm => JavascriptNpm::delete_dep(folder, group, name).is_ok(),
+    /// Read which packages each package in the lockfile depends on, for
+    /// package managers whose lockfiles record it
+    fn parse_dependency_graph(&self, _folder: &str) -> Option<DependencyGraph> {
+        None
     }
-}
 
-pub fn install_dep(kind: &ParserKind, folder: &str, dep: InstallCandidate) -> bool {
-    match kind {
-        ParserKind::RustCargo => RustCargo::install_dep(dep, folder).is_ok(),
-        ParserKind::JavascriptNpm => JavascriptNpm::install_dep(dep, folder).is_ok(),
+    /// Read the security advisories for the packages of this package
+    /// manager from a local copy of an advisory database, if there is one
+    fn load_advisories(&self) -> Vec<Advisory> {
+        vec![]
     }
 }
 
-pub async fn search_dep(
-    kind: &ParserKind,
-    term: &str,
-) -> Result<Vec<SearchDep>, Box<dyn std::error::Error>> {
-    match kind {
-        ParserKind::RustCargo => RustCargo::search_dep(term).await,
-        ParserKind::JavascriptNpm => JavascriptNpm::search_dep(term).await,
-    }
+/// All the supported package managers, in the order in which they are
+/// tried when detecting the kind of a project
+pub fn ecosystems() -> Vec<Arc<dyn Ecosystem>> {
+    vec![
+        Arc::new(JavascriptNpm),
+        Arc::new(RustCargo),
+        Arc::new(PythonPoetry),
+        Arc::new(PythonPip),
+        Arc::new(GoModules),
+        Arc::new(RubyBundler),
+        Arc::new(PhpComposer),
+    ]
 }
@@ -0,0 +1,509 @@
+use std::collections::hash_map::HashMap;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use lazy_static::lazy_static;
+use regex::Regex;
+use semver::Identifier;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::{
+    parser::{
+        version::{split_operator, Comparator, Op, Partial},
+        Author, Config, DepInfo, DependencyGroup, Lockfile, SearchDep, Version, VersionReq,
+    },
+    render::InstallCandidate,
+};
+
+use super::Ecosystem;
+
+/// For config file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ComposerJson {
+    name: Option<String>,
+    version: Option<String>,
+    require: Option<BTreeMap<String, String>>,
+    #[serde(rename = "require-dev")]
+    require_dev: Option<BTreeMap<String, String>>,
+}
+
+/// For lockfile
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct DepWithVersion {
+    name: String,
+    version: String,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ComposerLock {
+    #[serde(default)]
+    packages: Vec<DepWithVersion>,
+    #[serde(rename = "packages-dev", default)]
+    packages_dev: Vec<DepWithVersion>,
+}
+
+/// For metadata. Packagist minifies the list of versions so that only
+/// the first one has all the fields and the rest only have what changed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PackagistAuthor {
+    name: Option<String>,
+    email: Option<String>,
+    homepage: Option<String>,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PackagistSource {
+    url: Option<String>,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PackagistVersion {
+    version: String,
+    description: Option<String>,
+    homepage: Option<String>,
+    #[serde(default, deserialize_with = "unset_as_none")]
+    license: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "unset_as_none")]
+    authors: Option<Vec<PackagistAuthor>>,
+    #[serde(default, deserialize_with = "unset_as_none")]
+    source: Option<PackagistSource>,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PackagistResponse {
+    packages: BTreeMap<String, Vec<PackagistVersion>>,
+}
+
+/// Fields which a version removes compared to the one before it are set
+/// to `__unset` in the minified list
+fn unset_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        Value::String(s) if s == "__unset" => Ok(None),
+        other => T::deserialize(other)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+/// For search
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PackagistSearchResult {
+    name: String,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PackagistSearchResponse {
+    results: Vec<PackagistSearchResult>,
+}
+
+/// Packages without a vendor are platform packages like `php` or
+/// `ext-json` which are not available on Packagist
+fn is_platform_package(name: &str) -> bool {
+    !name.contains('/')
+}
+
+/// Parse a version like `1.2.3`, `v5.1.8`, `2.0.0-RC1` or `1.0.0-beta2`
+///
+/// Branches like `dev-master` or `2.x-dev` are not versions. Patch releases
+/// like `1.0.0-p1` and a fourth release number end up as build metadata.
+fn parse_version(version: &str) -> Option<Version> {
+    let (release, pre, build) = parse_parts(version)?;
+    let semver = semver::Version {
+        major: release[0],
+        minor: release.get(1).cloned().unwrap_or(0),
+        patch: release.get(2).cloned().unwrap_or(0),
+        pre,
+        build,
+    };
+    Some(Version::new(semver, version.trim()))
+}
+
+type VersionParts = (Vec<u64>, Vec<Identifier>, Vec<Identifier>);
+
+lazy_static! {
+    static ref VERSION_RE: Regex = Regex::new(
+        r"(?i)^v?(\d+(?:\.\d+){0,3})(?:[.-]?(stable|beta|b|rc|alpha|a|patch|pl|p)[.-]?(\d+)?)?$",
+    )
+    .unwrap();
+}
+
+fn parse_parts(version: &str) -> Option<VersionParts> {
+    let captures = VERSION_RE.captures(version.trim())?;
+    let release = captures[1]
+        .split('.')
+        .map(|x| x.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut pre = vec![];
+    let mut build: Vec<Identifier> = release
+        .iter()
+        .skip(3)
+        .map(|x| Identifier::Numeric(*x))
+        .collect();
+    let number = captures
+        .get(3)
+        .and_then(|x| x.as_str().parse::<u64>().ok())
+        .unwrap_or(0);
+    let label = captures.get(2).map(|x| x.as_str().to_lowercase());
+    match label.as_deref() {
+        Some("alpha") | Some("a") => pre.push(Identifier::AlphaNumeric("alpha".to_string())),
+        Some("beta") | Some("b") => pre.push(Identifier::AlphaNumeric("beta".to_string())),
+        Some("rc") => pre.push(Identifier::AlphaNumeric("rc".to_string())),
+        Some("patch") | Some("pl") | Some("p") => {
+            build.push(Identifier::AlphaNumeric("patch".to_string()))
+        }
+        _ => {}
+    }
+    if !pre.is_empty() {
+        pre.push(Identifier::Numeric(number));
+    } else if !build.is_empty() && label.is_some() {
+        build.push(Identifier::Numeric(number));
+    }
+    Some((release, pre, build))
+}
+
+/// Parse a version constraint like `^1.2`, `~1.2.3`, `>=1.0 <2.0`,
+/// `1.0 - 2.0`, `1.0.*` or `^1.0 || ^2.0`
+///
+/// Stability flags like `@beta` allow pre-releases of the lower bound.
+/// Branch constraints like `dev-master` are kept but do not match any
+/// version.
+fn parse_constraint(constraint: &str) -> Option<VersionReq> {
+    // Inline aliases like `dev-master as 1.0.x-dev` require the left side
+    let trimmed = constraint.split(" as ").next()?.trim();
+    let mut alternatives = vec![];
+    for alternative in trimmed.split('|').filter(|x| !x.trim().is_empty()) {
+        if alternative.trim().starts_with("dev-") || alternative.contains("-dev") {
+            continue;
+        }
+        let alternative = alternative.replace(" - ", "-hyphen-");
+        let mut comparators = vec![];
+        // Operators can be separated from the version by a space
+        let mut pending = String::new();
+        for token in alternative.split(|c: char| c == ',' || c.is_whitespace()) {
+            pending.push_str(token);
+            if pending.is_empty() || token.chars().all(|c| "<>=^~!".contains(c)) {
+                continue;
+            }
+            comparators.extend(parse_clause(&pending)?);
+            pending.clear();
+        }
+        alternatives.push(comparators);
+    }
+    Some(VersionReq::new(constraint.trim(), alternatives))
+}
+
+fn parse_clause(clause: &str) -> Option<Vec<Comparator>> {
+    let mut parts = clause.splitn(2, '@');
+    let clause = parts.next()?;
+    let mut comparators = parse_range(clause)?;
+    let stability = match parts.next().map(|x| x.to_lowercase()).as_deref() {
+        Some("dev") => Some(Identifier::Numeric(0)),
+        Some("alpha") => Some(Identifier::AlphaNumeric("alpha".to_string())),
+        Some("beta") => Some(Identifier::AlphaNumeric("beta".to_string())),
+        Some("rc") => Some(Identifier::AlphaNumeric("rc".to_string())),
+        _ => None,
+    };
+    if let Some(stability) = stability {
+        for comparator in comparators.iter_mut() {
+            if comparator.op == Op::GreaterEq && comparator.version.pre.is_empty() {
+                comparator.version.pre = vec![stability.clone()];
+            }
+        }
+    }
+    Some(comparators)
+}
+
+fn parse_range(clause: &str) -> Option<Vec<Comparator>> {
+    if let Some(pos) = clause.find("-hyphen-") {
+        let lower = partial(&clause[..pos])?;
+        let upper = partial(&clause[pos + "-hyphen-".len()..])?;
+        let mut comparators = lower.greater_eq();
+        comparators.extend(upper.less_eq());
+        return Some(comparators);
+    }
+
+    let (op, version) = split_operator(clause);
+    if version.is_empty() || version == "*" {
+        return Some(vec![]);
+    }
+    let partial = partial(version)?;
+    let wildcard = version.contains(['*', 'x', 'X']);
+    let complete = Partial {
+        major: partial.major.or(Some(0)),
+        minor: partial.minor.or(Some(0)),
+        patch: partial.patch.or(Some(0)),
+        pre: partial.pre.clone(),
+        build: partial.build.clone(),
+    };
+    Some(match op {
+        "" | "=" | "==" if wildcard => partial.exact(),
+        "" | "=" | "==" => complete.exact(),
+        "!=" | "<>" => complete.not_equal(),
+        "^" => partial.caret(),
+        "~" => partial.pessimistic(),
+        ">=" => complete.greater_eq(),
+        ">" => complete.greater(),
+        "<=" => complete.less_eq(),
+        "<" => complete.less(),
+        _ => return None,
+    })
+}
+
+fn partial(version: &str) -> Option<Partial> {
+    let version = version.trim().trim_start_matches(['v', 'V']);
+    if version.contains(['*', 'x', 'X']) {
+        return Partial::parse(version);
+    }
+    let (release, pre, build) = parse_parts(version)?;
+    Some(Partial {
+        build,
+        ..Partial::from_release(&release, pre)
+    })
+}
+
+/// The new constraint to write for a package, keeping the operator if it
+/// is a single constraint. Tilde constraints keep their precision as it
+/// changes what they allow.
+fn updated_constraint(constraint: &str, version: &str) -> String {
+    let version = version.trim_start_matches('v');
+    let (op, current) = split_operator(constraint.trim());
+    let single = !constraint.contains([',', '|', '*', ' ', '@']) && !current.is_empty();
+    match op {
+        "~" if single => {
+            let count = current.split('.').count();
+            let parts: Vec<&str> = version.split('.').take(count).collect();
+            format!("~{}", parts.join("."))
+        }
+        "" | "=" | "^" | ">=" if single => format!("{}{}", op, version),
+        _ => format!("^{}", version),
+    }
+}
+
+fn group(deps: BTreeMap<String, String>) -> DependencyGroup {
+    let mut group: DependencyGroup = BTreeMap::new();
+    for (name, constraint) in deps {
+        if !is_platform_package(&name) {
+            group.insert(name, parse_constraint(&constraint));
+        }
+    }
+    group
+}
+
+/// Composer writes its files with four spaces for indentation
+fn write_composer_json(path: &str, value: &Value) -> Result<(), Box<dyn std::error::Error>> {
+    let mut out = vec![];
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+    value.serialize(&mut serializer)?;
+    out.push(b'\n');
+    fs::write(path, out)?;
+    Ok(())
+}
+
+async fn fetch_packagist(name: &str) -> Result<Vec<PackagistVersion>, Box<dyn std::error::Error>> {
+    let mut url = format!("https://repo.packagist.org/p2/{}.json", name);
+    if env::var("MEAIN_TEST_ENV").is_ok() {
+        url = format!("http://localhost:8000/packagist/{}.json", name);
+    }
+    let mut resp: PackagistResponse = reqwest::Client::new()
+        .get(&url)
+        .header("User-Agent", "depman (github.com/meain/depman)")
+        .send()
+        .await?
+        .json()
+        .await?;
+    Ok(resp.packages.remove(name).unwrap_or_default())
+}
+
+pub struct PhpComposer;
+
+#[async_trait]
+impl Ecosystem for PhpComposer {
+    fn name(&self) -> &'static str {
+        "Composer"
+    }
+
+    fn is_this_it(&self, folder: &str) -> bool {
+        Path::new(&format!("{}/composer.json", folder)).exists()
+    }
+
+    fn parse_config(&self, folder: &str) -> Config {
+        let path_string = format!("{}/composer.json", folder);
+        let text = fs::read_to_string(&path_string)
+            .unwrap_or_else(|_| panic!("Unable to read {}", &path_string));
+        let parsed: ComposerJson = serde_json::from_str(&text)
+            .unwrap_or_else(|_| panic!("Unable to parse {}", &path_string));
+
+        let mut groups: BTreeMap<String, DependencyGroup> = BTreeMap::new();
+        if let Some(deps) = parsed.require {
+            groups.insert("require".to_string(), group(deps));
+        }
+        if let Some(deps) = parsed.require_dev {
+            groups.insert("require-dev".to_string(), group(deps));
+        }
+
+        Config {
+            name: parsed.name,
+            version: parsed.version.and_then(|x| parse_version(&x)),
+            groups,
+            sources: HashMap::new(),
+            features: HashMap::new(),
+            toolchain: None,
+        }
+    }
+
+    fn parse_lockfile(&self, folder: &str) -> Option<Lockfile> {
+        let path_string = format!("{}/composer.lock", folder);
+        let text = fs::read_to_string(&path_string).ok()?;
+        let parsed: ComposerLock = serde_json::from_str(&text)
+            .unwrap_or_else(|_| panic!("Unable to parse {}", &path_string));
+
+        let mut packages: Lockfile = HashMap::new();
+        for package in parsed.packages.into_iter().chain(parsed.packages_dev) {
+            if let Some(version) = parse_version(&package.version) {
+                packages.entry(package.name).or_default().push(version);
+            }
+        }
+        Some(packages)
+    }
+
+    async fn fetch_dep_info(&self, name: &str) -> Result<DepInfo, Box<dyn std::error::Error>> {
+        let releases = fetch_packagist(name).await?;
+        let latest = releases
+            .first()
+            .cloned()
+            .ok_or_else(|| format!("{} not found on Packagist", name))?;
+        let versions = releases
+            .iter()
+            .filter_map(|x| parse_version(&x.version))
+            .collect();
+
+        let author = latest
+            .authors
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|x| {
+                Some(Author {
+                    name: x.name?,
+                    url: x.homepage,
+                    email: x.email,
+                })
+            });
+        Ok(DepInfo {
+            name: name.to_string(),
+            author,
+            homepage: latest.homepage,
+            license: latest
+                .license
+                .map(|x| x.join(" OR "))
+                .filter(|x| !x.is_empty()),
+            description: latest.description,
+            repository: latest
+                .source
+                .and_then(|x| x.url)
+                .or_else(|| Some(format!("https://packagist.org/packages/{}", name))),
+            versions,
+            features: HashMap::new(),
+            withdrawn: HashMap::new(),
+            tags: HashMap::new(),
+            toolchain: HashMap::new(),
+            licenses: HashMap::new(),
+        })
+    }
+
+    fn delete_dep(
+        &self,
+        folder: &str,
+        group: &str,
+        name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path_string = format!("{}/composer.json", folder);
+        let data = fs::read_to_string(&path_string)?;
+        let mut composer_json: Value = serde_json::from_str(&data)?;
+        // Removing a key from the map directly would reorder the others
+        if let Value::Object(deps) = &mut composer_json[group] {
+            *deps = deps
+                .iter()
+                .filter(|(k, _)| k.as_str() != name)
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect();
+        }
+        write_composer_json(&path_string, &composer_json)
+    }
+
+    fn install_dep(
+        &self,
+        dep: InstallCandidate,
+        folder: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path_string = format!("{}/composer.json", folder);
+        let data = fs::read_to_string(&path_string)?;
+        let mut composer_json: Value = serde_json::from_str(&data)?;
+        let sort_packages = composer_json["config"]["sort-packages"] == Value::Bool(true);
+
+        let current = composer_json[&dep.kind][&dep.name]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+        let constraint = updated_constraint(&current, &dep.version);
+        composer_json[&dep.kind][&dep.name] = Value::String(constraint);
+        if sort_packages {
+            if let Value::Object(deps) = &mut composer_json[&dep.kind] {
+                let mut sorted: Vec<(String, Value)> = deps
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.clone()))
+                    .collect();
+                // Composer puts platform packages first, starting with php
+                sorted.sort_by_key(|(name, _)| {
+                    let priority = match name.as_str() {
+                        "php" => 0,
+                        n if is_platform_package(n) => 1,
+                        _ => 2,
+                    };
+                    (priority, name.to_string())
+                });
+                *deps = sorted.into_iter().collect();
+            }
+        }
+        write_composer_json(&path_string, &composer_json)
+    }
+
+    /// Search results from Packagist do not include versions, so the
+    /// latest stable version is looked up for the top results
+    async fn search_dep(&self, term: &str) -> Result<Vec<SearchDep>, Box<dyn std::error::Error>> {
+        let url = format!("https://packagist.org/search.json?per_page=10&q={}", term);
+        let resp: PackagistSearchResponse = reqwest::Client::new()
+            .get(&url)
+            .header("User-Agent", "depman (github.com/meain/depman)")
+            .send()
+            .await?
+            .json()
+            .await?;
+        let fetchers = resp
+            .results
+            .iter()
+            .map(|x| async move { fetch_packagist(&x.name).await.ok() });
+        let mut deps = vec![];
+        for (result, releases) in resp.results.iter().zip(join_all(fetchers).await) {
+            let latest = releases
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|x| parse_version(&x.version))
+                .filter(|x| x.pre.is_empty())
+                .max();
+            if let Some(version) = latest {
+                deps.push(SearchDep {
+                    name: result.name.to_string(),
+                    version: version.to_string(),
+                });
+            }
+        }
+        Ok(deps)
+    }
+}
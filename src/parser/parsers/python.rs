@@ -0,0 +1,475 @@
+//! Things shared by the Python package managers: PEP 440 versions and
+//! specifiers, package name normalization and the PyPI JSON API.
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+
+use semver::Identifier;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+
+use crate::parser::{
+    version::{split_operator, Comparator, Op, Partial},
+    Author, DepInfo, DependencySource, GitReference, SearchDep, Version, VersionReq,
+};
+
+/// For pulling versions
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PypiResponseInfo {
+    name: String,
+    summary: Option<String>,
+    license: Option<String>,
+    home_page: Option<String>,
+    author: Option<String>,
+    author_email: Option<String>,
+    project_urls: Option<BTreeMap<String, String>>,
+}
+#[derive(Deserialize, Debug)]
+struct PypiResponse {
+    info: PypiResponseInfo,
+    releases: BTreeMap<String, Vec<IgnoredAny>>,
+}
+
+/// Normalize a package name as described in PEP 503 so that `Django`,
+/// `django` and `DJANGO` all refer to the same package
+pub fn normalize_name(name: &str) -> String {
+    let mut normalized = String::new();
+    for c in name.trim().chars() {
+        if c == '-' || c == '_' || c == '.' {
+            if !normalized.ends_with('-') {
+                normalized.push('-');
+            }
+        } else {
+            normalized.push(c.to_ascii_lowercase());
+        }
+    }
+    normalized
+}
+
+/// Parse a PEP 440 version like `1.2`, `2.0.0rc1` or `1.0.post2`
+///
+/// Dev and pre-releases end up as semver pre-releases while extra release
+/// numbers and post releases are stored as build metadata. Both are
+/// encoded so that they sort the way PEP 440 orders them, except for
+/// pre-releases of versions with more than three release numbers which
+/// sort before the first three. Epochs and local versions are ignored.
+pub fn parse_version(version: &str) -> Option<Version> {
+    let (release, pre, build) = parse_parts(version)?;
+    let semver = semver::Version {
+        major: release[0],
+        minor: release.get(1).cloned().unwrap_or(0),
+        patch: release.get(2).cloned().unwrap_or(0),
+        pre,
+        build,
+    };
+    Some(Version::new(semver, version.trim()))
+}
+
+type VersionParts = (Vec<u64>, Vec<Identifier>, Vec<Identifier>);
+
+/// A post release sorts after the release it belongs to
+fn post_identifiers(post: Option<u64>) -> Vec<Identifier> {
+    match post {
+        Some(number) => vec![Identifier::Numeric(1), Identifier::Numeric(number)],
+        None => vec![Identifier::Numeric(0)],
+    }
+}
+
+/// A dev release sorts before the release it belongs to
+fn dev_identifiers(dev: Option<u64>) -> Vec<Identifier> {
+    match dev {
+        Some(number) => vec![Identifier::Numeric(0), Identifier::Numeric(number)],
+        None => vec![Identifier::Numeric(1)],
+    }
+}
+
+/// Final post releases are the only ones with a zero in their build
+/// metadata, since extra release numbers are shifted by one
+fn is_post_release(build: &[Identifier]) -> bool {
+    build.contains(&Identifier::Numeric(0))
+}
+
+fn parse_parts(version: &str) -> Option<VersionParts> {
+    let lowered = version.trim().to_lowercase();
+    let mut rest = lowered.trim_start_matches('v');
+    if let Some(pos) = rest.find('!') {
+        rest = &rest[pos + 1..];
+    }
+    rest = rest.split('+').next()?;
+
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(rest.len());
+    let release_str = rest[..end].trim_end_matches('.');
+    rest = &rest[release_str.len()..];
+    let release = release_str
+        .split('.')
+        .map(|x| x.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    // Phases of pre-releases in the order they come in
+    let pre_labels = [
+        ("alpha", 1),
+        ("a", 1),
+        ("beta", 2),
+        ("b", 2),
+        ("preview", 3),
+        ("pre", 3),
+        ("rc", 3),
+        ("c", 3),
+    ];
+    let phase = take_segment(rest, &pre_labels).map(|(phase, number, r)| {
+        rest = r;
+        (phase, number)
+    });
+
+    let post_labels = [("post", ()), ("rev", ()), ("r", ())];
+    let mut post = None;
+    if let Some((_, number, r)) = take_segment(rest, &post_labels) {
+        post = Some(number);
+        rest = r;
+    } else if rest.starts_with('-')
+        && rest.len() > 1
+        && rest[1..].chars().all(|c| c.is_ascii_digit())
+    {
+        post = Some(rest[1..].parse().ok()?);
+        rest = "";
+    }
+
+    let mut dev = None;
+    if let Some((_, number, r)) = take_segment(rest, &[("dev", ())]) {
+        dev = Some(number);
+        rest = r;
+    }
+
+    if !rest.is_empty() {
+        return None;
+    }
+
+    // Extra release numbers are shifted by one so that a zero can mark the
+    // start of a post release, which comes before any of them
+    let mut extra = release.iter().skip(3).cloned().collect::<Vec<_>>();
+    while extra.last() == Some(&0) {
+        extra.pop();
+    }
+    let mut build: Vec<Identifier> = extra.iter().map(|x| Identifier::Numeric(x + 1)).collect();
+    let mut pre = vec![];
+    match (phase, post, dev) {
+        (Some((phase, number)), _, _) => {
+            pre.push(Identifier::Numeric(phase));
+            pre.push(Identifier::Numeric(number));
+            pre.extend(post_identifiers(post));
+            pre.extend(dev_identifiers(dev));
+        }
+        (None, Some(number), _) => {
+            build.push(Identifier::Numeric(0));
+            build.push(Identifier::Numeric(number));
+            build.extend(dev_identifiers(dev));
+        }
+        // Dev releases come before all the pre-releases
+        (None, None, Some(number)) => {
+            pre.push(Identifier::Numeric(0));
+            pre.push(Identifier::Numeric(number));
+        }
+        (None, None, None) => {}
+    }
+    Some((release, pre, build))
+}
+
+/// Take a segment like `.post1`, `rc2` or `-dev` off the start of a
+/// version, returning what the label maps to, the number and what is left
+fn take_segment<'a, T: Copy>(version: &'a str, labels: &[(&str, T)]) -> Option<(T, u64, &'a str)> {
+    let rest = version.trim_start_matches(['.', '-', '_']);
+    for (label, value) in labels {
+        if let Some(rest) = rest.strip_prefix(label) {
+            let rest = rest.trim_start_matches(['.', '-', '_']);
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let number = if end == 0 {
+                0
+            } else {
+                rest[..end].parse().ok()?
+            };
+            return Some((*value, number, &rest[end..]));
+        }
+    }
+    None
+}
+
+/// Parse a PEP 440 version specifier like `>=1.2,!=1.3.*,<2` or `~=1.4`
+///
+/// An empty specifier matches every version.
+pub fn parse_specifier(specifier: &str) -> Option<VersionReq> {
+    let specifier = specifier.trim();
+    if specifier.is_empty() {
+        return Some(VersionReq::new("*", vec![vec![]]));
+    }
+
+    let mut comparators = vec![];
+    for clause in specifier.split(',') {
+        comparators.extend(parse_clause(clause)?);
+    }
+    Some(VersionReq::new(specifier, vec![comparators]))
+}
+
+/// Parse a single clause of a specifier like `>=1.2` or `==1.3.*`
+///
+/// On top of the PEP 440 operators this understands the ones used by
+/// Poetry: `^1.2`, `~1.2`, `*` and bare versions, which are exact.
+pub fn parse_clause(clause: &str) -> Option<Vec<Comparator>> {
+    let (op, version) = split_operator(clause.trim());
+    if version == "*" {
+        return Some(vec![]);
+    }
+    let wildcard = version.ends_with(".*");
+    let (release, pre, build) = parse_parts(version.trim_end_matches(".*"))?;
+    let partial = Partial::from_release(&release, pre);
+    // Extra release numbers and post releases are kept as build metadata
+    let complete = Partial {
+        build,
+        ..Partial::from_release(
+            &[
+                release[0],
+                release.get(1).cloned().unwrap_or(0),
+                release.get(2).cloned().unwrap_or(0),
+            ],
+            partial.pre.clone(),
+        )
+    };
+    Some(match op {
+        "" | "=" | "==" | "===" if wildcard => partial.exact(),
+        "" | "=" | "==" | "===" => complete.exact(),
+        "!=" if wildcard => vec![],
+        "!=" => complete.not_equal(),
+        // Only the last release number may change, even past the third
+        "~=" if release.len() > 3 => {
+            let mut comparators = complete.greater_eq();
+            let upper = semver::Version::new(release[0], release[1], release[2] + 1);
+            comparators.push(Comparator::new(Op::Less, upper));
+            comparators
+        }
+        "~=" => partial.pessimistic(),
+        "^" => partial.caret(),
+        "~" => partial.tilde(),
+        ">=" => complete.greater_eq(),
+        // Post releases of the version itself don't count as greater
+        ">" if !is_post_release(&complete.build) => {
+            let mut after = complete.lower();
+            after.build.push(Identifier::Numeric(1));
+            vec![Comparator::new(Op::GreaterEq, after)]
+        }
+        ">" => complete.greater(),
+        "<=" => complete.less_eq(),
+        "<" => complete.less(),
+        _ => return None,
+    })
+}
+
+/// Find the end of the requirement part of a line, that is before any
+/// environment markers, per-requirement options or comments
+pub fn requirement_end(line: &str) -> usize {
+    let mut end = line.len();
+    if let Some(pos) = line.find(';') {
+        end = end.min(pos);
+    }
+    if let Some(pos) = line.find(" --") {
+        end = end.min(pos);
+    }
+    if let Some(pos) = line.find(" #") {
+        end = end.min(pos);
+    }
+    if let Some(pos) = line.find('\\') {
+        end = end.min(pos);
+    }
+    end
+}
+
+/// Parse a PEP 508 requirement like `name[extras] >=1.2 ; marker` into its
+/// name, the specifier and the position of the specifier in the file
+/// given the offset of the line
+pub fn parse_requirement(line: &str, offset: usize) -> Option<(String, String, (usize, usize))> {
+    let end = requirement_end(line);
+    let start = line.len() - line.trim_start().len();
+    let text = &line[..end];
+
+    let name_end = text[start..]
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.'))
+        .map(|x| x + start)
+        .unwrap_or(end);
+    let name = &text[start..name_end];
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut spec_start = name_end;
+    let rest = &text[spec_start..];
+    if rest.trim_start().starts_with('[') {
+        spec_start += rest.find(']')? + 1;
+    }
+    let rest = &text[spec_start..];
+    // Direct references like `name @ https://...` have no version
+    if rest.trim_start().starts_with('@') {
+        return Some((
+            name.to_string(),
+            "".to_string(),
+            (offset + end, offset + end),
+        ));
+    }
+
+    let trimmed = rest.trim();
+    let (spec_start, spec_end) = if trimmed.is_empty() {
+        (spec_start, spec_start)
+    } else {
+        let s = spec_start + rest.find(trimmed).unwrap_or(0);
+        (s, s + trimmed.len())
+    };
+    let specifier = text[spec_start..spec_end]
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .trim()
+        .to_string();
+    Some((
+        name.to_string(),
+        specifier,
+        (offset + spec_start, offset + spec_end),
+    ))
+}
+
+/// Check if a requirement is a direct reference like `name @ https://...`,
+/// which points at a file instead of a version
+pub fn is_direct_reference(line: &str) -> bool {
+    line[..requirement_end(line)].contains('@')
+}
+
+/// The URL of a direct reference like `name @ https://...`
+pub fn direct_reference_url(line: &str) -> Option<&str> {
+    let text = &line[..requirement_end(line)];
+    let pos = text.find('@')?;
+    Some(text[pos + 1..].trim())
+}
+
+/// Where the URL of a direct reference points to, like
+/// `git+https://host/repo.git@v1.0` or `file:///some/folder`. Archives
+/// and local files count as paths.
+pub fn direct_reference_source(name: &str, url: &str) -> DependencySource {
+    let url = url.split('#').next().unwrap_or(url).trim();
+    if let Some(git) = url.strip_prefix("git+") {
+        // The revision is after an `@` in the path, the user comes before it
+        let host = git.find("://").map(|x| x + 3).unwrap_or(0);
+        let path = git[host..].find('/').map(|x| x + host).unwrap_or(git.len());
+        let (url, reference) = match git[path..].rfind('@') {
+            Some(pos) => (
+                &git[..path + pos],
+                Some(GitReference::Rev(git[path + pos + 1..].to_string())),
+            ),
+            None => (git, None),
+        };
+        return DependencySource::Git {
+            package: name.to_string(),
+            url: url.to_string(),
+            reference,
+            locked: None,
+        };
+    }
+    DependencySource::Path {
+        package: name.to_string(),
+        path: url.strip_prefix("file://").unwrap_or(url).to_string(),
+    }
+}
+
+/// The new specifier to write for a requirement, keeping the operator if
+/// it pins a single version and using `default` otherwise
+pub fn updated_specifier(specifier: &str, version: &str, default: &str) -> String {
+    let specifier = specifier.trim();
+    let (op, current) = split_operator(specifier);
+    let single = !specifier.contains([',', '|', '*']) && !current.contains(char::is_whitespace);
+    let keep = ["", "=", "==", "===", ">=", "~=", "^", "~"].contains(&op);
+    if !specifier.is_empty() && single && keep {
+        return format!("{}{}", op, version);
+    }
+    format!("{}{}", default, version)
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|x| !x.trim().is_empty() && x != "UNKNOWN")
+}
+
+async fn fetch_pypi(name: &str) -> Result<Option<PypiResponse>, Box<dyn std::error::Error>> {
+    let mut url = format!("https://pypi.org/pypi/{}/json", name);
+    if env::var("MEAIN_TEST_ENV").is_ok() {
+        url = format!("http://localhost:8000/pypi/{}.json", name);
+    }
+    let resp = reqwest::Client::new()
+        .get(&url)
+        .header("User-Agent", "depman (github.com/meain/depman)")
+        .send()
+        .await?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    Ok(Some(resp.json().await?))
+}
+
+pub async fn fetch_dep_info(name: &str) -> Result<DepInfo, Box<dyn std::error::Error>> {
+    let resp = fetch_pypi(name)
+        .await?
+        .ok_or_else(|| format!("{} not found on PyPI", name))?;
+
+    // Releases without any files can't be installed
+    let versions = resp
+        .releases
+        .iter()
+        .filter(|(_, files)| !files.is_empty())
+        .filter_map(|(version, _)| parse_version(version))
+        .collect();
+
+    let info = resp.info;
+    let project_urls = info.project_urls.unwrap_or_default();
+    let homepage = non_empty(info.home_page).or_else(|| project_urls.get("Homepage").cloned());
+    let email = non_empty(info.author_email);
+    let author = non_empty(info.author).map(|name| Author {
+        name,
+        url: None,
+        email,
+    });
+    let license = non_empty(info.license).map(|x| x.lines().next().unwrap_or("").to_string());
+
+    Ok(DepInfo {
+        name: name.to_string(),
+        author,
+        homepage,
+        license,
+        description: non_empty(info.summary),
+        repository: Some(format!("https://pypi.org/project/{}/", name)),
+        versions,
+        features: HashMap::new(),
+        withdrawn: HashMap::new(),
+        tags: HashMap::new(),
+        toolchain: HashMap::new(),
+        licenses: HashMap::new(),
+    })
+}
+
+/// PyPI does not have a search API, so all we can do is to look up the
+/// search term as a package name
+pub async fn search_dep(term: &str) -> Result<Vec<SearchDep>, Box<dyn std::error::Error>> {
+    let resp = match fetch_pypi(&normalize_name(term)).await? {
+        Some(resp) => resp,
+        None => return Ok(vec![]),
+    };
+    let latest = resp
+        .releases
+        .iter()
+        .filter(|(_, files)| !files.is_empty())
+        .filter_map(|(version, _)| parse_version(version))
+        .filter(|version| version.pre.is_empty())
+        .max();
+    Ok(match latest {
+        Some(version) => vec![SearchDep {
+            name: normalize_name(&resp.info.name),
+            version: version.to_string(),
+        }],
+        None => vec![],
+    })
+}
@@ -0,0 +1,380 @@
+use std::collections::hash_map::HashMap;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use async_trait::async_trait;
+
+use crate::{
+    parser::{Config, DepInfo, DependencyGroup, DependencySource, Lockfile, SearchDep},
+    render::InstallCandidate,
+};
+
+use super::python::{
+    self, direct_reference_source, direct_reference_url, normalize_name, parse_requirement,
+    parse_specifier, parse_version, requirement_end, updated_specifier,
+};
+use super::Ecosystem;
+
+/// A single requirement line (plus its continuation lines) from a
+/// requirements file
+#[derive(Debug, Clone)]
+struct Requirement {
+    name: String,
+    specifier: String,
+    /// Byte range of the specifier within the file
+    specifier_span: (usize, usize),
+    /// Range of lines making up the requirement including trailing comments
+    /// like the `# via` lines from pip-compile
+    lines: (usize, usize),
+    /// Where direct references and editable installs point to
+    source: Option<DependencySource>,
+}
+
+#[derive(Debug, Clone)]
+enum Line {
+    Requirement(Requirement),
+    Include(String),
+}
+
+fn parse_lines(text: &str) -> Vec<Line> {
+    let physical: Vec<&str> = text.split('\n').collect();
+    let mut offsets = vec![];
+    let mut offset = 0;
+    for line in physical.iter() {
+        offsets.push(offset);
+        offset += line.len() + 1;
+    }
+
+    let mut lines = vec![];
+    let mut i = 0;
+    while i < physical.len() {
+        let start = i;
+        while physical[i].trim_end().ends_with('\\') && i + 1 < physical.len() {
+            i += 1;
+        }
+        i += 1;
+        let first = physical[start].trim_end_matches('\r');
+        let trimmed = first.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let editable = match trimmed.strip_prefix("--editable") {
+            Some(rest) => Some(rest.trim_start_matches('=')),
+            None => trimmed.strip_prefix("-e"),
+        };
+        if trimmed.starts_with('-') && editable.is_none() {
+            let include = match trimmed.strip_prefix("--requirement") {
+                Some(rest) => Some(rest.trim_start_matches('=')),
+                None => trimmed.strip_prefix("-r"),
+            };
+            if let Some(path) = include {
+                let path = path[..requirement_end(path)].trim();
+                lines.push(Line::Include(path.to_string()));
+            }
+            continue;
+        }
+
+        // Comments indented under a requirement belong to it
+        while i < physical.len() && physical[i].starts_with(char::is_whitespace) {
+            if !physical[i].trim_start().starts_with('#') {
+                break;
+            }
+            i += 1;
+        }
+
+        if let Some(target) = editable {
+            let target = target[..requirement_end(target)].trim();
+            if let Some(name) = editable_name(target) {
+                let end = offsets[start] + requirement_end(first);
+                lines.push(Line::Requirement(Requirement {
+                    source: Some(direct_reference_source(&name, target)),
+                    name,
+                    specifier: "".to_string(),
+                    specifier_span: (end, end),
+                    lines: (start, i),
+                }));
+            }
+        } else if let Some((name, specifier, specifier_span)) =
+            parse_requirement(first, offsets[start])
+        {
+            lines.push(Line::Requirement(Requirement {
+                source: direct_reference_url(first).map(|x| direct_reference_source(&name, x)),
+                name,
+                specifier,
+                specifier_span,
+                lines: (start, i),
+            }));
+        }
+    }
+    lines
+}
+
+/// Name of the package in an editable install, from the `#egg=` part of
+/// the URL or else the last part of the path like `mylib` for `../mylib`
+fn editable_name(target: &str) -> Option<String> {
+    if let Some(pos) = target.find("#egg=") {
+        let egg = target[pos + "#egg=".len()..].split(['&', '[']).next()?;
+        return Some(egg.to_string()).filter(|x| !x.is_empty());
+    }
+    let location = match direct_reference_source("", target) {
+        DependencySource::Git { url, .. } => url,
+        DependencySource::Path { path, .. } => path,
+        DependencySource::Registry { .. } => return None,
+    };
+    let last = location.trim_end_matches('/').rsplit('/').next()?;
+    let last = last.trim_end_matches(".git");
+    if last.is_empty() || last.starts_with('.') {
+        None
+    } else {
+        Some(last.to_string())
+    }
+}
+
+fn read_lines(folder: &str, file: &str) -> Vec<Line> {
+    match fs::read_to_string(format!("{}/{}", folder, file)) {
+        Ok(text) => parse_lines(&text),
+        Err(_) => vec![],
+    }
+}
+
+fn is_requirements_file(name: &str, extension: &str) -> bool {
+    name.ends_with(extension) && name.contains("requirements")
+}
+
+/// Find all the requirements files in the project along with everything
+/// that they include, returned as paths relative to the project folder.
+///
+/// If there are pip-compile input files (`.in`), those are the config and
+/// the compiled `.txt` files are treated as the lockfile.
+fn requirement_files(folder: &str) -> Vec<String> {
+    let mut candidates = vec![];
+    for dir in &["", "requirements/"] {
+        if let Ok(entries) = fs::read_dir(format!("{}/{}", folder, dir)) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let in_dir = !dir.is_empty();
+                if in_dir || name.contains("requirements") {
+                    candidates.push(format!("{}{}", dir, name));
+                }
+            }
+        }
+    }
+    candidates.sort();
+
+    let has_inputs = candidates.iter().any(|x| x.ends_with(".in"));
+    let extension = if has_inputs { ".in" } else { ".txt" };
+    let mut files: Vec<String> = candidates
+        .into_iter()
+        .filter(|x| {
+            (x.starts_with("requirements/") && x.ends_with(extension))
+                || is_requirements_file(x, extension)
+        })
+        .collect();
+
+    let mut i = 0;
+    while i < files.len() {
+        let file = files[i].to_string();
+        let dir = match Path::new(&file).parent() {
+            Some(p) if p != Path::new("") => format!("{}/", p.to_string_lossy()),
+            _ => "".to_string(),
+        };
+        for line in read_lines(folder, &file) {
+            if let Line::Include(include) = line {
+                let included = normalize_path(&format!("{}{}", dir, include));
+                if !files.contains(&included)
+                    && Path::new(&format!("{}/{}", folder, included)).exists()
+                {
+                    files.push(included);
+                }
+            }
+        }
+        i += 1;
+    }
+    files
+}
+
+/// Remove `.` and `..` segments from a relative path
+fn normalize_path(path: &str) -> String {
+    let mut parts: Vec<&str> = vec![];
+    for part in path.split('/') {
+        match part {
+            "." | "" => {}
+            ".." if !parts.is_empty() && parts[parts.len() - 1] != ".." => {
+                parts.pop();
+            }
+            p => parts.push(p),
+        }
+    }
+    parts.join("/")
+}
+
+/// The file holding the pinned versions for a requirements file
+fn lockfile_for(folder: &str, file: &str) -> Option<String> {
+    if let Some(stem) = file.strip_suffix(".in") {
+        let compiled = format!("{}.txt", stem);
+        if Path::new(&format!("{}/{}", folder, compiled)).exists() {
+            return Some(compiled);
+        }
+        return None;
+    }
+    Some(file.to_string())
+}
+
+pub struct PythonPip;
+
+#[async_trait]
+impl Ecosystem for PythonPip {
+    fn name(&self) -> &'static str {
+        "pip"
+    }
+
+    fn is_this_it(&self, folder: &str) -> bool {
+        !requirement_files(folder).is_empty()
+    }
+
+    fn parse_config(&self, folder: &str) -> Config {
+        let mut groups: BTreeMap<String, DependencyGroup> = BTreeMap::new();
+        let mut sources = HashMap::new();
+        for file in requirement_files(folder) {
+            let mut group: DependencyGroup = BTreeMap::new();
+            let mut group_sources = HashMap::new();
+            for line in read_lines(folder, &file) {
+                if let Line::Requirement(req) = line {
+                    group.insert(normalize_name(&req.name), parse_specifier(&req.specifier));
+                    if let Some(source) = req.source {
+                        group_sources.insert(normalize_name(&req.name), source);
+                    }
+                }
+            }
+            groups.insert(file.to_string(), group);
+            sources.insert(file, group_sources);
+        }
+
+        Config {
+            name: None,
+            version: None,
+            groups,
+            sources,
+            features: HashMap::new(),
+            toolchain: None,
+        }
+    }
+
+    fn parse_lockfile(&self, folder: &str) -> Option<Lockfile> {
+        let mut packages: Lockfile = HashMap::new();
+        for file in requirement_files(folder) {
+            let lockfile = match lockfile_for(folder, &file) {
+                Some(lf) => lf,
+                None => continue,
+            };
+            for line in read_lines(folder, &lockfile) {
+                if let Line::Requirement(req) = line {
+                    let spec = req.specifier.trim();
+                    if spec.starts_with("==") && !spec.contains(',') && !spec.contains('*') {
+                        if let Some(version) = parse_version(spec.trim_start_matches('=')) {
+                            packages
+                                .entry(normalize_name(&req.name))
+                                .or_default()
+                                .push(version);
+                        }
+                    }
+                }
+            }
+        }
+        Some(packages)
+    }
+
+    async fn fetch_dep_info(&self, name: &str) -> Result<DepInfo, Box<dyn std::error::Error>> {
+        python::fetch_dep_info(name).await
+    }
+
+    fn delete_dep(
+        &self,
+        folder: &str,
+        group: &str,
+        name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path_string = format!("{}/{}", folder, group);
+        let text = fs::read_to_string(&path_string)?;
+        let mut remove = vec![];
+        for line in parse_lines(&text) {
+            if let Line::Requirement(req) = line {
+                if normalize_name(&req.name) == name {
+                    remove.push(req.lines);
+                }
+            }
+        }
+        let lines: Vec<&str> = text
+            .split('\n')
+            .enumerate()
+            .filter(|(i, _)| !remove.iter().any(|(start, end)| i >= start && i < end))
+            .map(|(_, line)| line)
+            .collect();
+        fs::write(&path_string, lines.join("\n"))?;
+        Ok(())
+    }
+
+    fn install_dep(
+        &self,
+        dep: InstallCandidate,
+        folder: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path_string = format!("{}/{}", folder, dep.kind);
+        let mut text = fs::read_to_string(&path_string)?;
+        let existing = parse_lines(&text).into_iter().find_map(|line| match line {
+            Line::Requirement(req) if normalize_name(&req.name) == normalize_name(&dep.name) => {
+                Some(req)
+            }
+            _ => None,
+        });
+        match existing {
+            Some(req) if req.source.is_some() => {
+                return Err(
+                    format!("{} is a direct reference instead of a version", dep.name).into(),
+                );
+            }
+            Some(req) => {
+                let (start, end) = req.specifier_span;
+                let specifier = updated_specifier(&req.specifier, &dep.version, "==");
+                // The hashes are for the files of the old version and pip
+                // refuses to install anything without hashes once some
+                // requirement has them
+                let hashed = text
+                    .split('\n')
+                    .skip(req.lines.0)
+                    .take(req.lines.1 - req.lines.0)
+                    .any(|line| line.contains("--hash"));
+                if hashed && specifier != req.specifier {
+                    return Err(format!(
+                        "{} has hashes in {}, regenerate them with pip-compile --generate-hashes",
+                        dep.name, dep.kind
+                    )
+                    .into());
+                }
+                text.replace_range(start..end, &specifier);
+            }
+            None => {
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                text.push_str(&format!("{}=={}\n", dep.name, dep.version));
+            }
+        }
+        fs::write(&path_string, text)?;
+        Ok(())
+    }
+
+    async fn search_dep(&self, term: &str) -> Result<Vec<SearchDep>, Box<dyn std::error::Error>> {
+        python::search_dep(term).await
+    }
+
+    fn install_notice(&self, folder: &str, group: &str) -> Option<String> {
+        let compiled = lockfile_for(folder, group).filter(|x| x != group)?;
+        Some(format!(
+            "Dependency updated! Run pip-compile to update {}",
+            compiled
+        ))
+    }
+}
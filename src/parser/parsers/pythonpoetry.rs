@@ -0,0 +1,407 @@
+use std::collections::hash_map::HashMap;
+use std::collections::BTreeMap;
+use std::fs;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use toml::Value;
+use toml_edit::{decorated, value, Document, Item};
+
+use crate::{
+    parser::{
+        Config, DepInfo, DependencyGroup, DependencySource, GitReference, Lockfile, SearchDep,
+        VersionReq,
+    },
+    render::InstallCandidate,
+};
+
+use super::python::{
+    self, direct_reference_source, direct_reference_url, is_direct_reference, normalize_name,
+    parse_clause, parse_requirement, parse_specifier, parse_version, updated_specifier,
+};
+use super::Ecosystem;
+
+/// For lockfile, the same for poetry.lock, pdm.lock and uv.lock
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct DepWithVersion {
+    name: String,
+    version: Option<String>,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct LockFile {
+    #[serde(default)]
+    package: Vec<DepWithVersion>,
+}
+
+/// Dependencies of a group which are not from PyPI
+type Sources = HashMap<String, DependencySource>;
+
+fn read_pyproject(folder: &str) -> Option<Value> {
+    let text = fs::read_to_string(format!("{}/pyproject.toml", folder)).ok()?;
+    toml::from_str(&text).ok()
+}
+
+/// Parse a Poetry version constraint like `^1.2`, `>=1.2,<1.5`,
+/// `>= 1.2 < 1.5` or `^1.0 || ^2.0`
+fn parse_constraint(constraint: &str) -> Option<VersionReq> {
+    let mut alternatives = vec![];
+    for alternative in constraint.split('|').filter(|x| !x.trim().is_empty()) {
+        let mut comparators = vec![];
+        for part in alternative.split(',') {
+            // Operators can be separated from the version by a space
+            let mut pending = String::new();
+            for token in part.split_whitespace() {
+                pending.push_str(token);
+                if token.chars().all(|c| "<>=^~!".contains(c)) {
+                    continue;
+                }
+                comparators.extend(parse_clause(&pending)?);
+                pending.clear();
+            }
+        }
+        alternatives.push(comparators);
+    }
+    if alternatives.is_empty() {
+        alternatives.push(vec![]);
+    }
+    Some(VersionReq::new(constraint.trim(), alternatives))
+}
+
+/// Version constraint of a dependency in one of the forms Poetry allows
+fn poetry_constraint(dep: &Value) -> Option<String> {
+    match dep {
+        Value::String(v) => Some(v.to_string()),
+        Value::Table(t) => t.get("version")?.as_str().map(|x| x.to_string()),
+        // Multiple constraints for different Python versions or platforms
+        Value::Array(items) => {
+            let versions: Vec<&str> = items
+                .iter()
+                .filter_map(|x| x.get("version")?.as_str())
+                .collect();
+            if versions.is_empty() {
+                None
+            } else {
+                Some(versions.join(" || "))
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Where a dependency comes from if it is not PyPI, for tables like
+/// `{ path = "../lib" }` or `{ git = "https://...", tag = "v1.0" }`.
+/// Archives at a `url` count as paths.
+fn poetry_source(name: &str, dep: &Value) -> Option<DependencySource> {
+    let table = dep.as_table()?;
+    let get = |key: &str| {
+        table
+            .get(key)
+            .and_then(|x| x.as_str())
+            .map(|x| x.to_string())
+    };
+    if let Some(url) = get("git") {
+        let reference = get("rev")
+            .map(GitReference::Rev)
+            .or_else(|| get("branch").map(GitReference::Branch))
+            .or_else(|| get("tag").map(GitReference::Tag));
+        return Some(DependencySource::Git {
+            package: name.to_string(),
+            url,
+            reference,
+            locked: None,
+        });
+    }
+    let path = get("path").or_else(|| get("url"))?;
+    Some(DependencySource::Path {
+        package: name.to_string(),
+        path,
+    })
+}
+
+fn poetry_group(deps: &toml::map::Map<String, Value>) -> (DependencyGroup, Sources) {
+    let mut group: DependencyGroup = BTreeMap::new();
+    let mut sources = HashMap::new();
+    for (name, dep) in deps {
+        // This is the constraint on the Python version
+        if name == "python" {
+            continue;
+        }
+        let version_req = poetry_constraint(dep).and_then(|x| parse_constraint(&x));
+        group.insert(normalize_name(name), version_req);
+        if let Some(source) = poetry_source(name, dep) {
+            sources.insert(normalize_name(name), source);
+        }
+    }
+    (group, sources)
+}
+
+fn pep621_group(deps: &[Value]) -> (DependencyGroup, Sources) {
+    let mut group: DependencyGroup = BTreeMap::new();
+    let mut sources = HashMap::new();
+    for dep in deps.iter().filter_map(|x| x.as_str()) {
+        if let Some((name, specifier, _)) = parse_requirement(dep, 0) {
+            group.insert(normalize_name(&name), parse_specifier(&specifier));
+            if let Some(url) = direct_reference_url(dep) {
+                sources.insert(normalize_name(&name), direct_reference_source(&name, url));
+            }
+        }
+    }
+    (group, sources)
+}
+
+/// Path to the TOML table or array which holds a dependency group
+fn group_path(group: &str) -> Vec<&str> {
+    let poetry_group = group
+        .strip_prefix("tool.poetry.group.")
+        .and_then(|x| x.strip_suffix(".dependencies"));
+    if let Some(name) = poetry_group {
+        return vec!["tool", "poetry", "group", name, "dependencies"];
+    }
+    if let Some(extra) = group.strip_prefix("project.optional-dependencies.") {
+        return vec!["project", "optional-dependencies", extra];
+    }
+    group.split('.').collect()
+}
+
+fn group_item<'a>(doc: &'a mut Document, group: &str) -> &'a mut Item {
+    let mut item = &mut doc.root;
+    for key in group_path(group) {
+        item = &mut item[key];
+    }
+    item
+}
+
+/// Find the key used for a dependency in a Poetry table
+fn poetry_key(item: &Item, name: &str) -> Option<String> {
+    item.as_table()?
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .find(|key| normalize_name(key) == normalize_name(name))
+}
+
+/// Find the position of a dependency in a PEP 621 array along with the
+/// parsed requirement
+fn pep621_position(item: &Item, name: &str) -> Option<(usize, String, String, (usize, usize))> {
+    item.as_array()?.iter().enumerate().find_map(|(i, dep)| {
+        let (dep_name, specifier, span) = parse_requirement(dep.as_str()?, 0)?;
+        if normalize_name(&dep_name) == normalize_name(name) {
+            Some((i, dep.as_str()?.to_string(), specifier, span))
+        } else {
+            None
+        }
+    })
+}
+
+pub struct PythonPoetry;
+
+#[async_trait]
+impl Ecosystem for PythonPoetry {
+    fn name(&self) -> &'static str {
+        "Poetry"
+    }
+
+    fn is_this_it(&self, folder: &str) -> bool {
+        match read_pyproject(folder) {
+            Some(parsed) => {
+                parsed.get("tool").and_then(|x| x.get("poetry")).is_some()
+                    || parsed
+                        .get("project")
+                        .and_then(|x| x.get("dependencies"))
+                        .is_some()
+                    || parsed
+                        .get("project")
+                        .and_then(|x| x.get("optional-dependencies"))
+                        .is_some()
+            }
+            None => false,
+        }
+    }
+
+    fn parse_config(&self, folder: &str) -> Config {
+        let path_string = format!("{}/pyproject.toml", folder);
+        let text = fs::read_to_string(&path_string)
+            .unwrap_or_else(|_| panic!("Unable to read {}", &path_string));
+        let parsed: Value =
+            toml::from_str(&text).unwrap_or_else(|_| panic!("Unable to parse {}", &path_string));
+
+        let mut name = None;
+        let mut version = None;
+        let mut groups: BTreeMap<String, DependencyGroup> = BTreeMap::new();
+        let mut sources = HashMap::new();
+        let mut add_group = |key: String, (group, group_sources): (DependencyGroup, Sources)| {
+            groups.insert(key.clone(), group);
+            sources.insert(key, group_sources);
+        };
+
+        if let Some(poetry) = parsed.get("tool").and_then(|x| x.get("poetry")) {
+            name = poetry
+                .get("name")
+                .and_then(|x| x.as_str())
+                .map(|x| x.to_string());
+            version = poetry
+                .get("version")
+                .and_then(|x| x.as_str())
+                .and_then(parse_version);
+            for key in &["dependencies", "dev-dependencies"] {
+                if let Some(Value::Table(deps)) = poetry.get(key) {
+                    add_group(format!("tool.poetry.{}", key), poetry_group(deps));
+                }
+            }
+            if let Some(Value::Table(poetry_groups)) = poetry.get("group") {
+                for (group_name, group) in poetry_groups {
+                    if let Some(Value::Table(deps)) = group.get("dependencies") {
+                        add_group(
+                            format!("tool.poetry.group.{}.dependencies", group_name),
+                            poetry_group(deps),
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(project) = parsed.get("project") {
+            if let Some(n) = project.get("name").and_then(|x| x.as_str()) {
+                name = Some(n.to_string());
+            }
+            if let Some(v) = project.get("version").and_then(|x| x.as_str()) {
+                version = parse_version(v);
+            }
+            if let Some(Value::Array(deps)) = project.get("dependencies") {
+                add_group("project.dependencies".to_string(), pep621_group(deps));
+            }
+            if let Some(Value::Table(extras)) = project.get("optional-dependencies") {
+                for (extra, deps) in extras {
+                    if let Value::Array(deps) = deps {
+                        add_group(
+                            format!("project.optional-dependencies.{}", extra),
+                            pep621_group(deps),
+                        );
+                    }
+                }
+            }
+        }
+
+        Config {
+            name,
+            version,
+            groups,
+            sources,
+            features: HashMap::new(),
+            toolchain: None,
+        }
+    }
+
+    fn parse_lockfile(&self, folder: &str) -> Option<Lockfile> {
+        let (path_string, text) = ["poetry.lock", "pdm.lock", "uv.lock"]
+            .iter()
+            .map(|x| format!("{}/{}", folder, x))
+            .find_map(|x| Some((x.to_string(), fs::read_to_string(&x).ok()?)))?;
+        let parsed: LockFile =
+            toml::from_str(&text).unwrap_or_else(|_| panic!("Unable to parse {}", &path_string));
+
+        let mut packages: Lockfile = HashMap::new();
+        for package in parsed.package {
+            if let Some(version) = package.version.as_ref().and_then(|x| parse_version(x)) {
+                packages
+                    .entry(normalize_name(&package.name))
+                    .or_default()
+                    .push(version);
+            }
+        }
+        Some(packages)
+    }
+
+    async fn fetch_dep_info(&self, name: &str) -> Result<DepInfo, Box<dyn std::error::Error>> {
+        python::fetch_dep_info(name).await
+    }
+
+    fn delete_dep(
+        &self,
+        folder: &str,
+        group: &str,
+        name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path_string = format!("{}/pyproject.toml", folder);
+        let file_contents = fs::read_to_string(&path_string)?;
+        let mut doc = file_contents.parse::<Document>()?;
+        let item = group_item(&mut doc, group);
+        if let Some((i, _, _, _)) = pep621_position(item, name) {
+            if let Some(array) = item.as_array_mut() {
+                array.remove(i);
+            }
+        } else if let Some(key) = poetry_key(item, name) {
+            item[&key] = Item::None;
+        }
+        fs::write(&path_string, doc.to_string())?;
+        Ok(())
+    }
+
+    fn install_dep(
+        &self,
+        dep: InstallCandidate,
+        folder: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path_string = format!("{}/pyproject.toml", folder);
+        let file_contents = fs::read_to_string(&path_string)?;
+        let mut doc = file_contents.parse::<Document>()?;
+        let item = group_item(&mut doc, &dep.kind);
+
+        if item.is_value() {
+            // PEP 621 list of requirements
+            let (i, requirement, reference) = match pep621_position(item, &dep.name) {
+                Some((_, current, _, _)) if is_direct_reference(&current) => {
+                    return Err(
+                        format!("{} is a direct reference instead of a version", dep.name).into(),
+                    );
+                }
+                Some((i, current, specifier, (start, end))) => {
+                    let mut requirement = current;
+                    requirement.replace_range(
+                        start..end,
+                        &updated_specifier(&specifier, &dep.version, ">="),
+                    );
+                    (i, requirement, i)
+                }
+                None => {
+                    let requirement = format!("{}>={}", dep.name, dep.version);
+                    let array = item.as_array_mut().ok_or("Invalid dependency list")?;
+                    array.push(requirement.as_str());
+                    let i = array.len() - 1;
+                    (i, requirement, i.saturating_sub(1))
+                }
+            };
+            // Keep the formatting of the item being replaced, or of the one
+            // before it for new items
+            let decor = item[reference]
+                .as_value()
+                .map(|x| x.decor().clone())
+                .unwrap_or_default();
+            let new_value = decorated(requirement.as_str().into(), decor.prefix(), decor.suffix());
+            item[i] = Item::Value(new_value);
+        } else {
+            // Poetry table of dependencies
+            match poetry_key(item, &dep.name) {
+                Some(key) => {
+                    if item[&key]["version"].is_none() && !item[&key].is_str() {
+                        return Err(format!("{} has no single version to change", dep.name).into());
+                    }
+                    if item[&key]["version"].is_none() {
+                        let current = item[&key].as_str().unwrap_or("").to_string();
+                        item[&key] = value(updated_specifier(&current, &dep.version, "^"));
+                    } else {
+                        let current = item[&key]["version"].as_str().unwrap_or("").to_string();
+                        item[&key]["version"] =
+                            value(updated_specifier(&current, &dep.version, "^"));
+                    }
+                }
+                None => item[&dep.name] = value(format!("^{}", dep.version)),
+            }
+        }
+        fs::write(&path_string, doc.to_string())?;
+        Ok(())
+    }
+
+    async fn search_dep(&self, term: &str) -> Result<Vec<SearchDep>, Box<dyn std::error::Error>> {
+        python::search_dep(term).await
+    }
+}
@@ -0,0 +1,676 @@
+use std::collections::hash_map::HashMap;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+
+use async_trait::async_trait;
+use semver::Identifier;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    parser::{
+        version::{split_operator, Comparator, Op, Partial},
+        Author, Config, DepInfo, DependencyGroup, Lockfile, SearchDep, Version, VersionReq,
+    },
+    render::InstallCandidate,
+};
+
+use super::Ecosystem;
+
+/// For pulling package info
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct RubyGemsResponse {
+    name: String,
+    authors: Option<String>,
+    info: Option<String>,
+    licenses: Option<Vec<String>>,
+    homepage_uri: Option<String>,
+    source_code_uri: Option<String>,
+    project_uri: Option<String>,
+}
+/// For pulling versions
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct RubyGemsVersion {
+    number: String,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct RubyGemsSearchResult {
+    name: String,
+    version: String,
+}
+
+/// Parse a RubyGems version like `1.2.3`, `6.0.3.4` or `2.0.0.rc1`
+///
+/// Anything after the first segment with a letter in it makes up the
+/// pre-release. Release numbers after the third one are stored as build
+/// metadata.
+fn parse_version(version: &str) -> Option<Version> {
+    let (release, pre) = parse_parts(version)?;
+    let semver = semver::Version {
+        major: release[0],
+        minor: release.get(1).cloned().unwrap_or(0),
+        patch: release.get(2).cloned().unwrap_or(0),
+        pre,
+        build: release
+            .iter()
+            .skip(3)
+            .map(|x| Identifier::Numeric(*x))
+            .collect(),
+    };
+    Some(Version::new(semver, version.trim()))
+}
+
+fn parse_parts(version: &str) -> Option<(Vec<u64>, Vec<Identifier>)> {
+    // Segments are runs of digits or letters, so `1.0rc1` is `1.0.rc.1`
+    let mut segments: Vec<String> = vec![];
+    let mut current = String::new();
+    for c in version.trim().chars() {
+        if c == '.' || c == '-' {
+            if current.is_empty() {
+                return None;
+            }
+            segments.push(std::mem::take(&mut current));
+        } else if c.is_ascii_alphanumeric() {
+            let last = current.chars().last();
+            if last.map(|l| l.is_ascii_digit() != c.is_ascii_digit()) == Some(true) {
+                segments.push(std::mem::take(&mut current));
+            }
+            current.push(c);
+        } else {
+            return None;
+        }
+    }
+    if current.is_empty() {
+        return None;
+    }
+    segments.push(current);
+
+    let mut release = vec![];
+    let mut pre = vec![];
+    for segment in segments {
+        match segment.parse::<u64>() {
+            Ok(n) if pre.is_empty() => release.push(n),
+            Ok(n) => pre.push(Identifier::Numeric(n)),
+            Err(_) => pre.push(Identifier::AlphaNumeric(segment)),
+        }
+    }
+    if release.is_empty() {
+        return None;
+    }
+    Some((release, pre))
+}
+
+/// Parse a list of requirements like `["~> 1.2", ">= 1.2.3"]`, all of which
+/// have to match. No requirements at all matches every version.
+fn parse_requirements(requirements: &[String]) -> Option<VersionReq> {
+    let mut comparators = vec![];
+    for requirement in requirements {
+        let (op, version) = split_operator(requirement.trim());
+        let (release, pre) = parse_parts(version)?;
+        let partial = Partial::from_release(&release, pre);
+        // Release numbers after the third one are build metadata like in
+        // the versions themselves
+        let complete = Partial {
+            build: release
+                .iter()
+                .skip(3)
+                .map(|x| Identifier::Numeric(*x))
+                .collect(),
+            ..Partial::from_release(
+                &[
+                    release[0],
+                    release.get(1).cloned().unwrap_or(0),
+                    release.get(2).cloned().unwrap_or(0),
+                ],
+                partial.pre.clone(),
+            )
+        };
+        comparators.extend(match op {
+            "" | "=" => complete.exact(),
+            "!=" => complete.not_equal(),
+            // Only the last release number may change, even past the third
+            "~>" if release.len() > 3 => {
+                let mut upper = complete.lower();
+                upper.pre = vec![];
+                upper.build.pop();
+                match upper.build.pop() {
+                    Some(Identifier::Numeric(n)) => upper.build.push(Identifier::Numeric(n + 1)),
+                    _ => upper.increment_patch(),
+                }
+                let mut comparators = complete.greater_eq();
+                comparators.push(Comparator::new(Op::Less, upper));
+                comparators
+            }
+            "~>" => partial.pessimistic(),
+            ">=" => complete.greater_eq(),
+            ">" => complete.greater(),
+            "<=" => complete.less_eq(),
+            "<" => complete.less(),
+            _ => return None,
+        });
+    }
+    let raw = if requirements.is_empty() {
+        "*".to_string()
+    } else {
+        requirements.join(", ")
+    };
+    Some(VersionReq::new(&raw, vec![comparators]))
+}
+
+/// The new requirement to write for a gem. A single `~>` requirement
+/// keeps its precision, so `~> 1.2` moves to `~> 1.4` rather than to
+/// `~> 1.4.2`. Anything else becomes what `bundle add` would write.
+fn updated_requirements(requirements: &[String], version: &str) -> Vec<String> {
+    let segments: Vec<&str> = version.split('.').collect();
+    let pessimistic =
+        |count: usize| format!("~> {}", segments[..count.min(segments.len())].join("."));
+    match requirements {
+        [single] => {
+            let (op, current) = split_operator(single.trim());
+            match op {
+                "~>" => vec![pessimistic(current.split('.').count())],
+                "" | "=" | ">=" => vec![format!("{} {}", op, version).trim().to_string()],
+                _ => vec![pessimistic(2)],
+            }
+        }
+        [] => vec![pessimistic(2)],
+        _ => vec![pessimistic(2), format!(">= {}", version)],
+    }
+}
+
+/// A string, symbol or any other argument in a Gemfile statement along
+/// with its byte range in the file
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Str(String),
+    Symbol(String),
+    /// The `key:` or `:key =>` part of a keyword argument
+    Key(String),
+    Comma,
+    Other(String),
+}
+
+/// Split a Ruby statement into tokens, stopping at a comment
+fn tokenize(code: &str, offset: usize) -> Vec<(Token, (usize, usize))> {
+    let bytes = code.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        let start = i;
+        if c.is_whitespace() || c == '(' || c == ')' || c == '[' || c == ']' {
+            i += 1;
+            continue;
+        }
+        if c == '#' {
+            break;
+        }
+        if c == ',' {
+            tokens.push((Token::Comma, (offset + i, offset + i + 1)));
+            i += 1;
+            continue;
+        }
+        if c == '\'' || c == '"' {
+            let end = code[i + 1..]
+                .find(c)
+                .map(|x| x + i + 1)
+                .unwrap_or(code.len());
+            let content = code[i + 1..end].to_string();
+            i = (end + 1).min(code.len());
+            tokens.push((Token::Str(content), (offset + start, offset + i)));
+            continue;
+        }
+        let end = code[i..]
+            .find(|c: char| c.is_whitespace() || ",()[]#".contains(c))
+            .map(|x| x + i)
+            .unwrap_or(code.len());
+        let word = &code[i..end];
+        i = end;
+        let token = if let Some(key) = word.strip_suffix(':') {
+            Token::Key(key.to_string())
+        } else if word == "=>" {
+            match tokens.pop() {
+                Some((Token::Symbol(key), _)) => Token::Key(key),
+                _ => Token::Other(word.to_string()),
+            }
+        } else if let Some(symbol) = word.strip_prefix(':') {
+            Token::Symbol(symbol.to_string())
+        } else {
+            Token::Other(word.to_string())
+        };
+        tokens.push((token, (offset + start, offset + end)));
+    }
+    tokens
+}
+
+/// A `gem` line from the Gemfile
+#[derive(Debug, Clone)]
+struct Gem {
+    name: String,
+    requirements: Vec<String>,
+    /// Byte range of the requirement strings including the quotes, or an
+    /// empty range right after the name if there are none
+    requirements_span: (usize, usize),
+    quote: char,
+    groups: Vec<String>,
+    /// Range of lines the statement spans
+    lines: (usize, usize),
+}
+
+/// A `group ... do` block from the Gemfile
+#[derive(Debug, Clone)]
+struct GroupBlock {
+    groups: Vec<String>,
+    /// Lines of the `group` statement and of the matching `end`
+    start: usize,
+    end: usize,
+    /// Whether the block is at the top level of the Gemfile
+    top_level: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Gemfile {
+    gems: Vec<Gem>,
+    blocks: Vec<GroupBlock>,
+}
+
+fn group_key(groups: &[String]) -> String {
+    if groups.is_empty() {
+        "default".to_string()
+    } else {
+        groups.join(", ")
+    }
+}
+
+fn symbols(tokens: &[(Token, (usize, usize))]) -> Vec<String> {
+    tokens
+        .iter()
+        .filter_map(|(t, _)| match t {
+            Token::Symbol(s) | Token::Str(s) => Some(s.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn parse_gem(tokens: &[(Token, (usize, usize))], groups: &[String]) -> Option<Gem> {
+    let (name, name_span) = match tokens.get(1) {
+        Some((Token::Str(name), span)) => (name.to_string(), *span),
+        _ => return None,
+    };
+    let mut gem = Gem {
+        name,
+        requirements: vec![],
+        requirements_span: (name_span.1, name_span.1),
+        quote: '"',
+        groups: groups.to_vec(),
+        lines: (0, 0),
+    };
+
+    let mut rest = &tokens[2..];
+    // Requirements are the strings before the first keyword argument
+    while let [(Token::Comma, _), (Token::Str(requirement), span), ..] = rest {
+        if gem.requirements.is_empty() {
+            gem.requirements_span.0 = span.0;
+        }
+        gem.requirements.push(requirement.to_string());
+        gem.requirements_span.1 = span.1;
+        rest = &rest[2..];
+    }
+
+    for (i, (token, _)) in rest.iter().enumerate() {
+        if let Token::Key(key) = token {
+            if key == "group" || key == "groups" {
+                let end = rest[i + 1..]
+                    .iter()
+                    .position(|(t, _)| matches!(t, Token::Key(_)))
+                    .map(|x| x + i + 1)
+                    .unwrap_or(rest.len());
+                gem.groups = symbols(&rest[i + 1..end]);
+            }
+        }
+    }
+    Some(gem)
+}
+
+fn opens_block(code: &str) -> bool {
+    let first = code.split_whitespace().next().unwrap_or("");
+    let words: Vec<&str> = code.split_whitespace().collect();
+    ["if", "unless", "case", "while", "until", "begin", "def"].contains(&first)
+        || words.last() == Some(&"do")
+        || (words.len() >= 2
+            && words[words.len() - 2] == "do"
+            && words[words.len() - 1].starts_with('|'))
+}
+
+fn parse_gemfile(text: &str) -> Gemfile {
+    let physical: Vec<&str> = text.split('\n').collect();
+    let mut offsets = vec![];
+    let mut offset = 0;
+    for line in physical.iter() {
+        offsets.push(offset);
+        offset += line.len() + 1;
+    }
+
+    let mut gems = vec![];
+    let mut blocks: Vec<GroupBlock> = vec![];
+    // Open blocks, pointing to the group block if it is one
+    let mut stack: Vec<Option<usize>> = vec![];
+    let mut i = 0;
+    while i < physical.len() {
+        let start = i;
+        // Statements continue onto the next line after a trailing comma
+        let mut end = offsets[start] + physical[start].len();
+        while i + 1 < physical.len() && code_part(physical[i]).trim_end().ends_with(',') {
+            i += 1;
+            end = offsets[i] + physical[i].len();
+        }
+        i += 1;
+
+        let statement = &text[offsets[start]..end];
+        let code = code_part(statement).trim();
+        let first = code
+            .split(|c: char| c.is_whitespace() || c == '(')
+            .next()
+            .unwrap_or("");
+        let groups: Vec<String> = stack
+            .iter()
+            .filter_map(|x| x.as_ref())
+            .flat_map(|x| blocks[*x].groups.clone())
+            .collect();
+
+        if code == "end" || code.starts_with("end ") || code.starts_with("end.") {
+            if let Some(Some(block)) = stack.pop() {
+                blocks[block].end = start;
+            }
+        } else if first == "group" && opens_block(code) {
+            let tokens = tokenize(statement, offsets[start]);
+            let block_groups = symbols(&tokens[1..]);
+            stack.push(Some(blocks.len()));
+            blocks.push(GroupBlock {
+                groups: block_groups,
+                start,
+                end: start,
+                top_level: stack.len() == 1,
+            });
+        } else if first == "gem" {
+            let tokens = tokenize(statement, offsets[start]);
+            if let Some(mut gem) = parse_gem(&tokens, &groups) {
+                gem.lines = (start, i);
+                gem.quote = statement
+                    .chars()
+                    .find(|c| *c == '\'' || *c == '"')
+                    .unwrap_or('\'');
+                gems.push(gem);
+            }
+        } else if opens_block(code) {
+            stack.push(None);
+        }
+    }
+    Gemfile { gems, blocks }
+}
+
+/// Everything in a line before a comment
+fn code_part(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn gem_line(name: &str, version: &str, indent: &str, quote: char) -> String {
+    let requirement = &updated_requirements(&[], version)[0];
+    format!(
+        "{}gem {q}{}{q}, {q}{}{q}",
+        indent,
+        name,
+        requirement,
+        q = quote
+    )
+}
+
+pub struct RubyBundler;
+
+#[async_trait]
+impl Ecosystem for RubyBundler {
+    fn name(&self) -> &'static str {
+        "Bundler"
+    }
+
+    fn is_this_it(&self, folder: &str) -> bool {
+        std::path::Path::new(&format!("{}/Gemfile", folder)).exists()
+    }
+
+    fn parse_config(&self, folder: &str) -> Config {
+        let path_string = format!("{}/Gemfile", folder);
+        let text = fs::read_to_string(&path_string)
+            .unwrap_or_else(|_| panic!("Unable to read {}", &path_string));
+
+        let mut groups: BTreeMap<String, DependencyGroup> = BTreeMap::new();
+        for gem in parse_gemfile(&text).gems {
+            groups
+                .entry(group_key(&gem.groups))
+                .or_default()
+                .insert(gem.name, parse_requirements(&gem.requirements));
+        }
+
+        Config {
+            name: None,
+            version: None,
+            groups,
+            sources: HashMap::new(),
+            features: HashMap::new(),
+            toolchain: None,
+        }
+    }
+
+    /// Resolved versions are listed under `specs:` in the `GEM`, `GIT` and
+    /// `PATH` sections, with the dependencies of each gem indented below it
+    fn parse_lockfile(&self, folder: &str) -> Option<Lockfile> {
+        let text = fs::read_to_string(format!("{}/Gemfile.lock", folder)).ok()?;
+        let mut packages: Lockfile = HashMap::new();
+        let mut in_specs = false;
+        for line in text.lines() {
+            let indent = line.len() - line.trim_start().len();
+            if indent <= 2 {
+                in_specs = line.trim() == "specs:";
+                continue;
+            }
+            if !in_specs || indent != 4 {
+                continue;
+            }
+            let mut parts = line.trim().splitn(2, ' ');
+            let name = parts.next().unwrap_or("");
+            let version = parts
+                .next()
+                .unwrap_or("")
+                .trim_start_matches('(')
+                .trim_end_matches(')');
+            // Platform specific gems have the platform after the version
+            let version = version.split('-').next().unwrap_or("");
+            if let Some(version) = parse_version(version) {
+                packages.entry(name.to_string()).or_default().push(version);
+            }
+        }
+        Some(packages)
+    }
+
+    async fn fetch_dep_info(&self, name: &str) -> Result<DepInfo, Box<dyn std::error::Error>> {
+        let mut url = format!("https://rubygems.org/api/v1/gems/{}.json", name);
+        let mut versions_url = format!("https://rubygems.org/api/v1/versions/{}.json", name);
+        if env::var("MEAIN_TEST_ENV").is_ok() {
+            url = format!("http://localhost:8000/rubygems/{}.json", name);
+            versions_url = format!("http://localhost:8000/rubygems/versions/{}.json", name);
+        }
+        let client = reqwest::Client::new();
+        let resp: RubyGemsResponse = client
+            .get(&url)
+            .header("User-Agent", "depman (github.com/meain/depman)")
+            .send()
+            .await?
+            .json()
+            .await?;
+        let versions_resp: Vec<RubyGemsVersion> = client
+            .get(&versions_url)
+            .header("User-Agent", "depman (github.com/meain/depman)")
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        // There is an entry for each platform a version is released for
+        let mut versions: Vec<Version> = vec![];
+        for version in versions_resp {
+            if let Some(v) = parse_version(&version.number) {
+                if !versions.iter().any(|x| x.to_string() == v.to_string()) {
+                    versions.push(v);
+                }
+            }
+        }
+
+        Ok(DepInfo {
+            name: name.to_string(),
+            author: resp.authors.map(|name| Author {
+                name,
+                url: None,
+                email: None,
+            }),
+            homepage: resp.homepage_uri.or(resp.project_uri),
+            license: resp
+                .licenses
+                .map(|x| x.join(", "))
+                .filter(|x| !x.is_empty()),
+            description: resp.info,
+            repository: resp
+                .source_code_uri
+                .or_else(|| Some(format!("https://rubygems.org/gems/{}", name))),
+            versions,
+            features: HashMap::new(),
+            withdrawn: HashMap::new(),
+            tags: HashMap::new(),
+            toolchain: HashMap::new(),
+            licenses: HashMap::new(),
+        })
+    }
+
+    fn delete_dep(
+        &self,
+        folder: &str,
+        group: &str,
+        name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path_string = format!("{}/Gemfile", folder);
+        let text = fs::read_to_string(&path_string)?;
+        let remove: Vec<(usize, usize)> = parse_gemfile(&text)
+            .gems
+            .into_iter()
+            .filter(|x| x.name == name && group_key(&x.groups) == group)
+            .map(|x| x.lines)
+            .collect();
+        let lines: Vec<&str> = text
+            .split('\n')
+            .enumerate()
+            .filter(|(i, _)| !remove.iter().any(|(start, end)| i >= start && i < end))
+            .map(|(_, line)| line)
+            .collect();
+        fs::write(&path_string, lines.join("\n"))?;
+        Ok(())
+    }
+
+    fn install_dep(
+        &self,
+        dep: InstallCandidate,
+        folder: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path_string = format!("{}/Gemfile", folder);
+        let mut text = fs::read_to_string(&path_string)?;
+        let gemfile = parse_gemfile(&text);
+
+        let existing = gemfile
+            .gems
+            .iter()
+            .find(|x| x.name == dep.name && group_key(&x.groups) == dep.kind)
+            .or_else(|| gemfile.gems.iter().find(|x| x.name == dep.name));
+        if let Some(gem) = existing {
+            let quoted: Vec<String> = updated_requirements(&gem.requirements, &dep.version)
+                .into_iter()
+                .map(|x| format!("{}{}{}", gem.quote, x, gem.quote))
+                .collect();
+            let (start, end) = gem.requirements_span;
+            let replacement = if gem.requirements.is_empty() {
+                format!(", {}", quoted.join(", "))
+            } else {
+                quoted.join(", ")
+            };
+            text.replace_range(start..end, &replacement);
+            fs::write(&path_string, text)?;
+            return Ok(());
+        }
+
+        let mut lines: Vec<String> = text.split('\n').map(|x| x.to_string()).collect();
+        let quote = gemfile.gems.first().map(|x| x.quote).unwrap_or('"');
+        let block = gemfile
+            .blocks
+            .iter()
+            .find(|x| x.top_level && group_key(&x.groups) == dep.kind);
+        if dep.kind == "default" {
+            // Right after the last gem outside of any group
+            let position = gemfile
+                .gems
+                .iter()
+                .filter(|x| x.groups.is_empty())
+                .map(|x| x.lines.1)
+                .max();
+            match position {
+                Some(p) => lines.insert(p, gem_line(&dep.name, &dep.version, "", quote)),
+                None => lines.push(gem_line(&dep.name, &dep.version, "", quote)),
+            }
+        } else if let Some(block) = block {
+            let indent = gemfile
+                .gems
+                .iter()
+                .find(|x| x.lines.0 > block.start && x.lines.0 < block.end)
+                .map(|x| {
+                    let line = &lines[x.lines.0];
+                    line[..line.len() - line.trim_start().len()].to_string()
+                })
+                .unwrap_or_else(|| "  ".to_string());
+            lines.insert(block.end, gem_line(&dep.name, &dep.version, &indent, quote));
+        } else {
+            if lines.last().map(|x| x.is_empty()).unwrap_or(false) {
+                lines.pop();
+            }
+            let groups: Vec<String> = dep.kind.split(", ").map(|x| format!(":{}", x)).collect();
+            lines.push("".to_string());
+            lines.push(format!("group {} do", groups.join(", ")));
+            lines.push(gem_line(&dep.name, &dep.version, "  ", quote));
+            lines.push("end".to_string());
+            lines.push("".to_string());
+        }
+        fs::write(&path_string, lines.join("\n"))?;
+        Ok(())
+    }
+
+    async fn search_dep(&self, term: &str) -> Result<Vec<SearchDep>, Box<dyn std::error::Error>> {
+        let url = format!("https://rubygems.org/api/v1/search.json?query={}", term);
+        let resp: Vec<RubyGemsSearchResult> = reqwest::Client::new()
+            .get(&url)
+            .header("User-Agent", "depman (github.com/meain/depman)")
+            .send()
+            .await?
+            .json()
+            .await?;
+        Ok(resp
+            .into_iter()
+            .map(|x| SearchDep {
+                name: x.name,
+                version: x.version,
+            })
+            .collect())
+    }
+}
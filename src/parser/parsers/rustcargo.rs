@@ -1,41 +1,85 @@
 use std::collections::hash_map::HashMap;
 use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use semver::{Version, VersionReq};
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use toml::Value;
-use toml_edit::{value, Document};
+use toml_edit::{value, Array, Document, InlineTable, Item};
 
 use crate::{
-    parser::{Config, DepInfo, DependencyGroup, Lockfile, SearchDep},
+    parser::{
+        advisory, version::Partial, Advisory, Config, DepInfo, DependencyFeatures, DependencyGraph,
+        DependencyGroup, DependencySource, FeatureList, GitReference, LockedPackage, Lockfile,
+        SearchDep, Toolchain, Version, VersionReq, Withdrawal,
+    },
     render::InstallCandidate,
 };
 
+use super::Ecosystem;
+
 /// For lockfile
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 struct DepWithVersion {
     name: String,
     version: String,
+    source: Option<String>,
+    /// Written as `name`, `name version` or `name version (source)`,
+    /// with only as much as it takes to tell the copies apart
+    #[serde(default)]
+    dependencies: Vec<String>,
 }
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct LockFile {
     package: Vec<DepWithVersion>,
 }
 
+/// Read the lockfile, which members of a workspace share at its root
+fn read_lockfile(folder: &str) -> Option<LockFile> {
+    let root = workspace_root(Path::new(folder)).unwrap_or_else(|| PathBuf::from(folder));
+    let path_string = root.join("Cargo.lock").to_string_lossy().to_string();
+    let text = fs::read_to_string(&path_string).ok()?;
+    Some(toml::from_str(&text).unwrap_or_else(|_| panic!("Unable to parse {}", &path_string)))
+}
+
+/// Where the RustSec advisory-db is checked out. Unless `DEPMAN_RUSTSEC_DB`
+/// says otherwise, this is where cargo-audit keeps it.
+fn rustsec_db() -> Option<PathBuf> {
+    if let Ok(path) = env::var("DEPMAN_RUSTSEC_DB") {
+        return Some(PathBuf::from(path));
+    }
+    let cargo_home = env::var("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("HOME").map(|x| Path::new(&x).join(".cargo")))
+        .ok()?;
+    let folder = cargo_home.join("advisory-db");
+    if folder.is_dir() {
+        Some(folder)
+    } else {
+        None
+    }
+}
+
 /// For pulling versions
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct CargoResponseCrate {
     name: String,
     description: Option<String>,
-    license: Option<String>, // TODO: license is version specific
+    license: Option<String>, // of the newest version, see `licenses`
     homepage: Option<String>,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct CargoResponseVersion {
     num: String,
+    #[serde(default)]
+    features: FeatureList,
+    #[serde(default)]
+    yanked: bool,
+    rust_version: Option<String>,
+    license: Option<String>,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct CargoResponse {
@@ -56,109 +100,627 @@ struct CratesIOSearchResp {
     crates: Vec<CratesIOSearchCreate>,
 }
 
-pub struct RustCargo;
-impl RustCargo {
-    pub fn is_this_it(folder: &str) -> bool {
-        Path::new(&format!("{}/Cargo.toml", folder)).exists()
+/// Separates the path of a workspace member from the table in the name
+/// of its dependency groups, as in `crates/core: dependencies`
+const MEMBER_SEPARATOR: &str = ": ";
+
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+fn read_manifest(folder: &Path) -> Option<Value> {
+    let text = fs::read_to_string(folder.join("Cargo.toml")).ok()?;
+    toml::from_str(&text).ok()
+}
+
+/// Find the root of the workspace a package is part of, which is the
+/// closest folder, starting with the package itself, whose manifest has a
+/// `[workspace]` table
+fn workspace_root(folder: &Path) -> Option<PathBuf> {
+    let folder = fs::canonicalize(folder).ok()?;
+    folder
+        .ancestors()
+        .find(|x| matches!(read_manifest(x), Some(m) if m.get("workspace").is_some()))
+        .map(|x| x.to_path_buf())
+}
+
+/// Match a file name against a pattern which can use `*` and `?`
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| glob_match(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && glob_match(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && glob_match(rest, &name[1..]),
     }
+}
 
-    pub fn parse_config(folder: &str) -> Config {
-        let path_string = format!("{}/Cargo.toml", folder);
-        let text = fs::read_to_string(&path_string)
-            .unwrap_or_else(|_| panic!("Unable to read {}", &path_string));
-        let parsed: Value =
-            toml::from_str(&text).unwrap_or_else(|_| panic!("Unable to parse {}", &path_string));
+/// Expand a pattern like `crates/*` into the matching folders relative to
+/// `root`
+fn expand_glob(root: &Path, pattern: &str) -> Vec<String> {
+    let join = |path: &str, name: &str| {
+        if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", path, name)
+        }
+    };
+    let mut paths = vec![String::new()];
+    for component in pattern.split('/').filter(|x| !x.is_empty() && *x != ".") {
+        let chars: Vec<char> = component.chars().collect();
+        let mut matches = vec![];
+        for path in paths {
+            if !component.contains(['*', '?']) {
+                matches.push(join(&path, component));
+                continue;
+            }
+            let mut names: Vec<String> = fs::read_dir(root.join(&path))
+                .map(|entries| {
+                    entries
+                        .filter_map(|x| x.ok())
+                        .filter(|x| x.path().is_dir())
+                        .map(|x| x.file_name().to_string_lossy().to_string())
+                        .filter(|x| glob_match(&chars, &x.chars().collect::<Vec<_>>()))
+                        .collect()
+                })
+                .unwrap_or_default();
+            names.sort();
+            matches.extend(names.iter().map(|x| join(&path, x)));
+        }
+        paths = matches;
+    }
+    paths
+}
 
-        let mut name = None;
-        let mut version = None;
-        if let Some(v) = &parsed.get("package") {
-            if let Value::Table(pkg) = v {
-                if let Some(vv) = pkg.get("name") {
-                    if let Value::String(n) = vv {
-                        name = Some(n.to_string());
-                    }
+/// Paths of the members of the workspace at `root` relative to it, not
+/// including the package at the root itself
+fn workspace_members(root: &Path, workspace: &Value) -> Vec<String> {
+    let expand = |key: &str| -> Vec<String> {
+        workspace
+            .get(key)
+            .and_then(|x| x.as_array())
+            .map(|x| {
+                x.iter()
+                    .filter_map(|x| x.as_str())
+                    .flat_map(|x| expand_glob(root, x))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let excluded = expand("exclude");
+    let mut members: Vec<String> = expand("members")
+        .into_iter()
+        .filter(|x| !x.is_empty() && root.join(x).join("Cargo.toml").exists())
+        .filter(|x| {
+            !excluded
+                .iter()
+                .any(|e| x == e || x.starts_with(&format!("{}/", e)))
+        })
+        .collect();
+    members.sort();
+    members.dedup();
+    members
+}
+
+/// Version requirement of a dependency, which for dependencies with
+/// `workspace = true` comes from `[workspace.dependencies]`
+fn dependency_req(name: &str, dep: &Value, workspace_deps: Option<&Value>) -> Option<VersionReq> {
+    match dep {
+        Value::String(v) => VersionReq::parse(v).ok(),
+        Value::Table(t) => {
+            if t.get("workspace").and_then(|x| x.as_bool()) == Some(true) {
+                return dependency_req(name, workspace_deps?.get(name)?, None);
+            }
+            VersionReq::parse(t.get("version")?.as_str()?).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Where a dependency comes from if it is not the default registry under
+/// its own name. `locked` has the commits of git dependencies in the
+/// lockfile.
+fn dependency_source(
+    name: &str,
+    dep: &Value,
+    workspace_deps: Option<&Value>,
+    locked: &HashMap<String, String>,
+) -> Option<DependencySource> {
+    let table = dep.as_table()?;
+    if table.get("workspace").and_then(|x| x.as_bool()) == Some(true) {
+        return dependency_source(name, workspace_deps?.get(name)?, None, locked);
+    }
+    let get = |key: &str| {
+        table
+            .get(key)
+            .and_then(|x| x.as_str())
+            .map(|x| x.to_string())
+    };
+    let package = get("package").unwrap_or_else(|| name.to_string());
+    if let Some(url) = get("git") {
+        let reference = get("rev")
+            .map(GitReference::Rev)
+            .or_else(|| get("branch").map(GitReference::Branch))
+            .or_else(|| get("tag").map(GitReference::Tag));
+        return Some(DependencySource::Git {
+            locked: locked.get(&package).cloned(),
+            package,
+            url,
+            reference,
+        });
+    }
+    if let Some(path) = get("path") {
+        return Some(DependencySource::Path { package, path });
+    }
+    let registry = get("registry");
+    if registry.is_some() || package != name {
+        return Some(DependencySource::Registry { package, registry });
+    }
+    None
+}
+
+fn string_array(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(|x| {
+            x.iter()
+                .filter_map(|x| x.as_str())
+                .map(|x| x.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The `[features]` table of a package
+fn package_features(manifest: &Value) -> FeatureList {
+    manifest
+        .get("features")
+        .and_then(|x| x.as_table())
+        .map(|x| {
+            x.iter()
+                .map(|(name, enables)| (name.to_string(), string_array(enables)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Features of a package which turn on its optional dependency `name`,
+/// either directly or by turning on one of the other features that do
+fn enabling_features(name: &str, features: &FeatureList) -> Vec<String> {
+    let explicit = format!("dep:{}", name);
+    // Optional dependencies get a feature of their own unless some feature
+    // refers to them with `dep:`
+    let mut enabling = vec![];
+    if !features.values().flatten().any(|x| x == &explicit) {
+        enabling.push(name.to_string());
+    }
+    loop {
+        let found: Vec<String> = features
+            .iter()
+            .filter(|(feature, _)| !enabling.contains(feature))
+            .filter(|(_, enables)| {
+                enables.iter().any(|x| {
+                    x == &explicit
+                        || enabling.contains(x)
+                        || x.split_once('/').map(|(dep, _)| dep) == Some(name)
+                })
+            })
+            .map(|(feature, _)| feature.to_string())
+            .collect();
+        if found.is_empty() {
+            break;
+        }
+        enabling.extend(found);
+    }
+    enabling
+}
+
+/// Features enabled for a dependency. Dependencies with `workspace = true`
+/// add their own features to the ones in `[workspace.dependencies]`, but
+/// can only turn off default features from there.
+fn dependency_features(
+    name: &str,
+    dep: &Value,
+    workspace_deps: Option<&Value>,
+) -> DependencyFeatures {
+    let features = |dep: &Value| dep.get("features").map(string_array).unwrap_or_default();
+    let default_features = |dep: &Value| {
+        dep.get("default-features")
+            .or_else(|| dep.get("default_features"))
+            .and_then(|x| x.as_bool())
+            != Some(false)
+    };
+    let inherited = dep.get("workspace").and_then(|x| x.as_bool()) == Some(true);
+    let optional = dep.get("optional").and_then(|x| x.as_bool()) == Some(true);
+    match workspace_deps
+        .and_then(|x| x.get(name))
+        .filter(|_| inherited)
+    {
+        Some(root) => {
+            let mut enabled = features(root);
+            for feature in features(dep) {
+                if !enabled.contains(&feature) {
+                    enabled.push(feature);
                 }
-                if let Some(vv) = pkg.get("version") {
-                    if let Value::String(vvv) = vv {
-                        if let Ok(vvvv) = Version::parse(&vvv) {
-                            version = Some(vvvv);
-                        }
+            }
+            DependencyFeatures {
+                enabled,
+                default_features: default_features(root),
+                optional,
+                enabled_by: vec![],
+            }
+        }
+        None => DependencyFeatures {
+            enabled: features(dep),
+            default_features: default_features(dep),
+            optional,
+            enabled_by: vec![],
+        },
+    }
+}
+
+/// Collects the dependency groups of the manifests in a workspace along
+/// with the sources and features of the dependencies
+struct ManifestReader<'a> {
+    workspace_deps: Option<&'a Value>,
+    locked: HashMap<String, String>,
+    sources: HashMap<String, HashMap<String, DependencySource>>,
+    features: HashMap<String, HashMap<String, DependencyFeatures>>,
+}
+
+impl<'a> ManifestReader<'a> {
+    /// Read a table of dependencies, where `package_features` is the
+    /// `[features]` table of the package it is in
+    fn dependency_group(
+        &mut self,
+        id: &GroupId,
+        deps: &toml::value::Table,
+        package_features: &FeatureList,
+    ) -> DependencyGroup {
+        let mut group = BTreeMap::new();
+        let sources = self.sources.entry(id.to_string()).or_default();
+        let features = self.features.entry(id.to_string()).or_default();
+        for (name, dep) in deps {
+            if let Some(source) = dependency_source(name, dep, self.workspace_deps, &self.locked) {
+                sources.insert(name.to_string(), source);
+            }
+            let mut dep_features = dependency_features(name, dep, self.workspace_deps);
+            if dep_features.optional {
+                dep_features.enabled_by = enabling_features(name, package_features);
+            }
+            features.insert(name.to_string(), dep_features);
+            group.insert(
+                name.to_string(),
+                dependency_req(name, dep, self.workspace_deps),
+            );
+        }
+        group
+    }
+
+    /// All the dependency groups in the manifest of `member`, or of the
+    /// package in the folder itself if `None`
+    fn manifest_groups(
+        &mut self,
+        manifest: &Value,
+        member: Option<&str>,
+    ) -> Vec<(GroupId, DependencyGroup)> {
+        let package_features = package_features(manifest);
+        let mut groups = vec![];
+        for kind in &DEPENDENCY_TABLES {
+            if let Some(Value::Table(deps)) = manifest.get(kind) {
+                let id = GroupId::new(member, &[kind]);
+                let group = self.dependency_group(&id, deps, &package_features);
+                groups.push((id, group));
+            }
+        }
+        if let Some(Value::Table(target)) = manifest.get("target") {
+            for (cfg, tables) in target {
+                for kind in &DEPENDENCY_TABLES {
+                    if let Some(Value::Table(deps)) = tables.get(kind) {
+                        let id = GroupId::new(member, &["target", cfg, kind]);
+                        let group = self.dependency_group(&id, deps, &package_features);
+                        groups.push((id, group));
                     }
                 }
             }
         }
+        groups
+    }
+}
 
-        // TODO: Get all dep groups
-        let mut groups: BTreeMap<String, DependencyGroup> = BTreeMap::new();
-        if let Value::Table(conf) = parsed {
-            for key in conf.keys() {
-                if [
-                    "dependencies".to_string(),
-                    "dev-dependencies".to_string(),
-                    "build-dependencies".to_string(),
-                ]
-                .contains(key)
-                {
-                    if let Value::Table(gr) = &conf[key] {
-                        let mut group: BTreeMap<String, Option<VersionReq>> = BTreeMap::new();
-                        for dep in gr.keys() {
-                            let version_req = match &gr[dep] {
-                                Value::String(v) => match VersionReq::parse(&v) {
-                                    Ok(vr) => Some(vr),
-                                    Err(_) => None,
-                                },
-                                Value::Table(t) => {
-                                    if let Some(vs) = t.get("version") {
-                                        match vs {
-                                            Value::String(v) => VersionReq::parse(&v).ok(),
-                                            _ => None,
-                                        }
-                                    } else {
-                                        None
-                                    }
-                                }
-                                _ => None,
-                            };
-                            group.insert(dep.to_string(), version_req);
-                        }
-                        groups.insert(key.to_string(), group);
-                    }
+fn workspace_rust_version(manifest: &Value) -> Option<&str> {
+    manifest
+        .get("workspace")?
+        .get("package")?
+        .get("rust-version")?
+        .as_str()
+}
+
+/// The oldest Rust version the package in `manifest` supports, which it
+/// can inherit from `[workspace.package]` in `root_manifest`. Virtual
+/// manifests only have the one for the workspace.
+fn rust_version(manifest: &Value, root_manifest: Option<&Value>) -> Option<Toolchain> {
+    let version = match manifest.get("package") {
+        Some(package) => match package.get("rust-version")? {
+            Value::String(v) => v.as_str(),
+            v if v.get("workspace").and_then(|x| x.as_bool()) == Some(true) => {
+                workspace_rust_version(root_manifest?)?
+            }
+            _ => return None,
+        },
+        None => workspace_rust_version(manifest)?,
+    };
+    Some(Toolchain {
+        name: "rust".to_string(),
+        version: Version::new(Partial::parse(version)?.lower(), version),
+    })
+}
+
+/// Commits of the git dependencies in Cargo.lock
+fn locked_commits(root: &Path) -> HashMap<String, String> {
+    let parsed: Option<LockFile> = fs::read_to_string(root.join("Cargo.lock"))
+        .ok()
+        .and_then(|x| toml::from_str(&x).ok());
+    let mut commits = HashMap::new();
+    for package in parsed.map(|x| x.package).unwrap_or_default() {
+        let commit = package
+            .source
+            .filter(|x| x.starts_with("git+"))
+            .and_then(|x| Some(x[x.find('#')? + 1..].to_string()));
+        if let Some(commit) = commit {
+            commits.insert(package.name, commit);
+        }
+    }
+    commits
+}
+
+/// Identifies a dependency group by the workspace member it is in, if it
+/// is not the package in the folder itself, and the keys of its table.
+/// Written out, the keys are joined as a TOML dotted key like
+/// `target.'cfg(unix)'.dependencies`.
+#[derive(Debug, Clone, PartialEq)]
+struct GroupId {
+    member: Option<String>,
+    keys: Vec<String>,
+}
+
+impl GroupId {
+    fn new(member: Option<&str>, keys: &[&str]) -> GroupId {
+        GroupId {
+            member: member.map(|x| x.to_string()),
+            keys: keys.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    /// Read back the name of a group written out with `to_string`
+    fn parse(group: &str) -> Option<GroupId> {
+        let (member, mut rest) = match group.split_once(MEMBER_SEPARATOR) {
+            Some((member, table)) => (Some(member.to_string()), table),
+            None => (None, group),
+        };
+        let mut keys = vec![];
+        loop {
+            let (key, remaining) = match rest.chars().next()? {
+                '\'' => {
+                    let end = rest[1..].find('\'')? + 1;
+                    (rest[1..end].to_string(), &rest[end + 1..])
+                }
+                '"' => {
+                    let mut escaped = false;
+                    let (end, _) = rest.char_indices().skip(1).find(|(_, c)| {
+                        let found = *c == '"' && !escaped;
+                        escaped = *c == '\\' && !escaped;
+                        found
+                    })?;
+                    let parsed: Value = toml::from_str(&format!("key = {}", &rest[..=end])).ok()?;
+                    (parsed.get("key")?.as_str()?.to_string(), &rest[end + 1..])
                 }
+                _ => {
+                    let end = rest.find('.').unwrap_or(rest.len());
+                    (rest[..end].to_string(), &rest[end..])
+                }
+            };
+            keys.push(key);
+            match remaining.strip_prefix('.') {
+                Some(r) => rest = r,
+                None if remaining.is_empty() => break,
+                None => return None,
             }
+        }
+        Some(GroupId { member, keys })
+    }
+
+    /// Path to the manifest the group is in
+    fn manifest(&self, folder: &str) -> PathBuf {
+        let mut path = PathBuf::from(folder);
+        if let Some(member) = &self.member {
+            path.push(member);
+        }
+        path.join("Cargo.toml")
+    }
 
-            if let Some(t) = &conf.get("target") {
-                if let Value::Table(target) = t {
-                    for g in target.keys() {
-                        if let Some(ggg) = target.get(g).unwrap().get("dependencies") {
-                            if let Value::Table(gg) = ggg {
-                                let mut group: BTreeMap<String, Option<VersionReq>> =
-                                    BTreeMap::new();
-                                for dep in gg.keys() {
-                                    let version_req = match &gg[dep] {
-                                        Value::String(v) => match VersionReq::parse(&v) {
-                                            Ok(vr) => Some(vr),
-                                            Err(_) => None,
-                                        },
-                                        Value::Table(t) => {
-                                            if let Some(vs) = t.get("version") {
-                                                match vs {
-                                                    Value::String(v) => VersionReq::parse(&v).ok(),
-                                                    _ => None,
-                                                }
-                                            } else {
-                                                None
-                                            }
-                                        }
-                                        _ => None,
-                                    };
-                                    group.insert(dep.to_string(), version_req);
-                                }
-                                groups.insert(
-                                    format!("target.{}.dependencies", g.to_string()),
-                                    group,
-                                );
-                            }
-                        }
+    fn table<'a>(&self, manifest: &'a Value) -> Option<&'a Value> {
+        self.keys
+            .iter()
+            .try_fold(manifest, |item, key| item.get(key))
+    }
+
+    fn table_item<'a>(&self, doc: &'a mut Document) -> &'a mut Item {
+        let mut item = &mut doc.root;
+        for key in &self.keys {
+            item = &mut item[key];
+        }
+        item
+    }
+}
+
+impl fmt::Display for GroupId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(member) = &self.member {
+            write!(f, "{}{}", member, MEMBER_SEPARATOR)?;
+        }
+        let keys: Vec<String> = self
+            .keys
+            .iter()
+            .map(|key| {
+                let bare = !key.is_empty()
+                    && key
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+                if bare {
+                    key.to_string()
+                } else if !key.contains('\'') {
+                    format!("'{}'", key)
+                } else {
+                    Value::String(key.to_string()).to_string()
+                }
+            })
+            .collect();
+        write!(f, "{}", keys.join("."))
+    }
+}
+
+/// Set the version of a dependency, keeping the table form if it has one
+fn set_version(item: &mut Item, version: &str) {
+    if item["version"].is_none() {
+        *item = value(version);
+    } else {
+        item["version"] = value(version);
+    }
+}
+
+/// Remove `key` from a dependency written as a table or an inline table
+fn remove_key(item: &mut Item, key: &str) {
+    if let Some(table) = item.as_table_mut() {
+        table.remove(key);
+    } else if let Some(table) = item.as_inline_table_mut() {
+        table.remove(key);
+    }
+}
+
+/// Set the features of a dependency, switching it to the inline table form
+/// if it only has a version. `default_features` is left alone if `None`.
+fn set_features(item: &mut Item, features: &[String], default_features: Option<bool>) {
+    if let Some(version) = item.as_str().map(|x| x.to_string()) {
+        let mut table = InlineTable::default();
+        table.get_or_insert("version", version);
+        *item = value(table);
+    }
+    if features.is_empty() {
+        remove_key(item, "features");
+    } else {
+        let mut array = Array::default();
+        for feature in features {
+            array.push(feature.as_str());
+        }
+        item["features"] = value(array);
+    }
+    if let Some(default_features) = default_features {
+        remove_key(item, "default_features");
+        if default_features {
+            remove_key(item, "default-features");
+        } else {
+            item["default-features"] = value(false);
+        }
+    }
+    // Go back to the short form if there is nothing but the version left
+    let version = item.as_inline_table().and_then(|x| match x.iter().next() {
+        Some(("version", v)) if x.len() == 1 => v.as_str().map(|v| v.to_string()),
+        _ => None,
+    });
+    if let Some(version) = version {
+        *item = value(version);
+    } else if let Some(table) = item.as_inline_table_mut() {
+        table.fmt();
+    }
+}
+
+/// Features listed for a dependency in the manifest
+fn listed_features(item: &Item) -> Vec<String> {
+    item["features"]
+        .as_array()
+        .map(|x| {
+            x.iter()
+                .filter_map(|x| x.as_str())
+                .map(|x| x.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Check if a dependency gets its details from `[workspace.dependencies]`
+fn is_inherited(manifest: &Value, id: &GroupId, name: &str) -> bool {
+    id.table(manifest)
+        .and_then(|x| x.get(name))
+        .and_then(|x| x.get("workspace"))
+        .and_then(|x| x.as_bool())
+        == Some(true)
+}
+
+/// Path to the manifest at the root of the workspace `manifest` is in
+fn root_manifest(manifest: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let root = manifest
+        .parent()
+        .and_then(workspace_root)
+        .ok_or("Unable to find the workspace root")?;
+    Ok(root.join("Cargo.toml"))
+}
+
+pub struct RustCargo;
+
+#[async_trait]
+impl Ecosystem for RustCargo {
+    fn name(&self) -> &'static str {
+        "Cargo"
+    }
+
+    fn is_this_it(&self, folder: &str) -> bool {
+        Path::new(&format!("{}/Cargo.toml", folder)).exists()
+    }
+
+    fn parse_config(&self, folder: &str) -> Config {
+        let path_string = format!("{}/Cargo.toml", folder);
+        let text = fs::read_to_string(&path_string)
+            .unwrap_or_else(|_| panic!("Unable to read {}", &path_string));
+        let parsed: Value =
+            toml::from_str(&text).unwrap_or_else(|_| panic!("Unable to parse {}", &path_string));
+
+        let package = parsed.get("package");
+        let name = package
+            .and_then(|x| x.get("name"))
+            .and_then(|x| x.as_str())
+            .map(|x| x.to_string());
+        let version = package
+            .and_then(|x| x.get("version"))
+            .and_then(|x| x.as_str())
+            .and_then(|x| Version::parse(x).ok());
+
+        let root = workspace_root(Path::new(folder)).unwrap_or_else(|| PathBuf::from(folder));
+        let root_manifest = read_manifest(&root);
+        let mut reader = ManifestReader {
+            workspace_deps: root_manifest
+                .as_ref()
+                .and_then(|x| x.get("workspace"))
+                .and_then(|x| x.get("dependencies")),
+            locked: locked_commits(&root),
+            sources: HashMap::new(),
+            features: HashMap::new(),
+        };
+
+        let mut groups: BTreeMap<String, DependencyGroup> = reader
+            .manifest_groups(&parsed, None)
+            .into_iter()
+            .map(|(id, group)| (id.to_string(), group))
+            .collect();
+        if let Some(workspace) = parsed.get("workspace") {
+            if let Some(Value::Table(deps)) = workspace.get("dependencies") {
+                let id = GroupId::new(None, &["workspace", "dependencies"]);
+                groups.insert(
+                    id.to_string(),
+                    reader.dependency_group(&id, deps, &FeatureList::new()),
+                );
+            }
+            for member in workspace_members(Path::new(folder), workspace) {
+                if let Some(manifest) = read_manifest(&Path::new(folder).join(&member)) {
+                    for (id, group) in reader.manifest_groups(&manifest, Some(&member)) {
+                        groups.insert(id.to_string(), group);
                     }
                 }
             }
@@ -168,26 +730,70 @@ impl RustCargo {
             name,
             version,
             groups,
+            sources: reader.sources,
+            features: reader.features,
+            toolchain: rust_version(&parsed, root_manifest.as_ref()),
         }
     }
 
-    pub fn parse_lockfile(folder: &str) -> Option<Lockfile> {
-        let path_string = format!("{}/Cargo.lock", folder);
-        let text = fs::read_to_string(&path_string).ok()?;
-        let parsed: LockFile =
-            toml::from_str(&text).unwrap_or_else(|_| panic!("Unable to parse {}", &path_string));
-
-        let mut packages = HashMap::new();
+    fn parse_lockfile(&self, folder: &str) -> Option<Lockfile> {
+        let parsed = read_lockfile(folder)?;
+        let mut packages: Lockfile = HashMap::new();
         for package in parsed.package {
-            packages.insert(
-                package.name.to_string(),
-                Version::parse(&package.version).ok().unwrap(),
-            );
+            if let Ok(version) = Version::parse(&package.version) {
+                packages.entry(package.name).or_default().push(version);
+            }
         }
         Some(packages)
     }
 
-    pub async fn fetch_dep_info(name: &str) -> Result<DepInfo, Box<dyn std::error::Error>> {
+    fn parse_dependency_graph(&self, folder: &str) -> Option<DependencyGraph> {
+        let parsed = read_lockfile(folder)?;
+        let mut versions: HashMap<&str, Vec<&str>> = HashMap::new();
+        for package in &parsed.package {
+            versions
+                .entry(&package.name)
+                .or_default()
+                .push(&package.version);
+        }
+
+        let mut graph: DependencyGraph = HashMap::new();
+        for package in &parsed.package {
+            let dependencies = package
+                .dependencies
+                .iter()
+                .filter_map(|x| {
+                    let mut parts = x.split_whitespace();
+                    let name = parts.next()?;
+                    let version = match parts.next() {
+                        Some(v) => v,
+                        None => versions.get(name)?.first()?,
+                    };
+                    Some(LockedPackage {
+                        name: name.to_string(),
+                        version: version.to_string(),
+                    })
+                })
+                .collect();
+            graph.insert(
+                LockedPackage {
+                    name: package.name.to_string(),
+                    version: package.version.to_string(),
+                },
+                dependencies,
+            );
+        }
+        Some(graph)
+    }
+
+    fn load_advisories(&self) -> Vec<Advisory> {
+        match rustsec_db() {
+            Some(folder) => advisory::read_rustsec(&folder),
+            None => vec![],
+        }
+    }
+
+    async fn fetch_dep_info(&self, name: &str) -> Result<DepInfo, Box<dyn std::error::Error>> {
         let mut url = format!("https://crates.io/api/v1/crates/{}", name);
         if env::var("MEAIN_TEST_ENV").is_ok() {
             url = format!("http://localhost:8000/cargo/{}.json", name)
@@ -200,6 +806,30 @@ impl RustCargo {
             .json()
             .await?;
 
+        let features = resp
+            .versions
+            .iter()
+            .map(|x| (x.num.to_string(), x.features.clone()))
+            .collect();
+        let toolchain = resp
+            .versions
+            .iter()
+            .filter_map(|x| {
+                let requirement = VersionReq::parse(&format!(">={}", x.rust_version.as_ref()?));
+                Some((x.num.to_string(), requirement.ok()?))
+            })
+            .collect();
+        let licenses = resp
+            .versions
+            .iter()
+            .filter_map(|x| Some((x.num.to_string(), x.license.clone()?)))
+            .collect();
+        let withdrawn = resp
+            .versions
+            .iter()
+            .filter(|x| x.yanked)
+            .map(|x| (x.num.to_string(), Withdrawal::Yanked))
+            .collect();
         let versions = resp
             .versions
             .into_iter()
@@ -214,41 +844,58 @@ impl RustCargo {
             description: resp.info.description,
             repository: Some(format!("https://crates.io/crates/{}", name)),
             versions,
+            features,
+            withdrawn,
+            tags: HashMap::new(),
+            toolchain,
+            licenses,
         })
     }
 
-    pub fn delete_dep(
+    fn delete_dep(
+        &self,
         folder: &str,
         group: &str,
         name: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let path_string = format!("{}/Cargo.toml", folder);
-        let file_contents = std::fs::read_to_string(&path_string)?;
-        let mut doc = file_contents
-            .parse::<Document>()
-            .expect("Invalid config file");
-        doc[group][name] = toml_edit::Item::None;
-        std::fs::write(&path_string, doc.to_string())?;
+        let id = GroupId::parse(group).ok_or("Invalid dependency group")?;
+        let path = id.manifest(folder);
+        let file_contents = std::fs::read_to_string(&path)?;
+        let mut doc = file_contents.parse::<Document>()?;
+        id.table_item(&mut doc)[name] = Item::None;
+        std::fs::write(&path, doc.to_string())?;
         Ok(())
     }
 
-    pub fn install_dep(
+    fn install_dep(
+        &self,
         dep: InstallCandidate,
         folder: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let path_string = format!("{}/Cargo.toml", folder);
-        let file_contents = std::fs::read_to_string(&path_string)?;
-        let mut doc = file_contents.parse::<Document>()?;
-        if doc[&dep.kind][&dep.name]["version"].is_none() {
-            doc[&dep.kind][&dep.name] = value(dep.version);
-        } else {
-            doc[&dep.kind][&dep.name]["version"] = value(dep.version);
+        let id = GroupId::parse(&dep.kind).ok_or("Invalid dependency group")?;
+        let path = id.manifest(folder);
+        let file_contents = std::fs::read_to_string(&path)?;
+
+        // Inherited dependencies get their version from the workspace root
+        let manifest: Value = toml::from_str(&file_contents)?;
+        if is_inherited(&manifest, &id, &dep.name) {
+            let root_path = root_manifest(&path)?;
+            let mut doc = std::fs::read_to_string(&root_path)?.parse::<Document>()?;
+            set_version(
+                &mut doc["workspace"]["dependencies"][&dep.name],
+                &dep.version,
+            );
+            std::fs::write(&root_path, doc.to_string())?;
+            return Ok(());
         }
-        std::fs::write(&path_string, doc.to_string())?;
+
+        let mut doc = file_contents.parse::<Document>()?;
+        set_version(&mut id.table_item(&mut doc)[&dep.name], &dep.version);
+        std::fs::write(&path, doc.to_string())?;
         Ok(())
     }
 
-    pub async fn search_dep(term: &str) -> Result<Vec<SearchDep>, Box<dyn std::error::Error>> {
+    async fn search_dep(&self, term: &str) -> Result<Vec<SearchDep>, Box<dyn std::error::Error>> {
         let url = format!(
             "https://crates.io/api/v1/crates?page=1&per_page=20&q={}",
             term
@@ -269,4 +916,49 @@ impl RustCargo {
             })
             .collect())
     }
+    fn update_features(
+        &self,
+        folder: &str,
+        group: &str,
+        name: &str,
+        features: &DependencyFeatures,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let id = GroupId::parse(group).ok_or("Invalid dependency group")?;
+        let path = id.manifest(folder);
+        let file_contents = std::fs::read_to_string(&path)?;
+        let manifest: Value = toml::from_str(&file_contents)?;
+        if id.table(&manifest).and_then(|x| x.get(name)).is_none() {
+            return Err(format!("{} is not in {}", name, group).into());
+        }
+        let mut doc = file_contents.parse::<Document>()?;
+
+        // Inherited dependencies can add features to the ones from the
+        // workspace root, but default features are only set at the root
+        if is_inherited(&manifest, &id, name) {
+            let root_path = root_manifest(&path)?;
+            let mut root_doc = std::fs::read_to_string(&root_path)?.parse::<Document>()?;
+            let root_item = &mut root_doc["workspace"]["dependencies"][name];
+            let root_features: Vec<String> = listed_features(root_item)
+                .into_iter()
+                .filter(|x| features.enabled.contains(x))
+                .collect();
+            set_features(root_item, &root_features, Some(features.default_features));
+            let own_features: Vec<String> = features
+                .enabled
+                .iter()
+                .filter(|x| !root_features.contains(x))
+                .cloned()
+                .collect();
+            set_features(&mut id.table_item(&mut doc)[name], &own_features, None);
+            std::fs::write(&root_path, root_doc.to_string())?;
+        } else {
+            set_features(
+                &mut id.table_item(&mut doc)[name],
+                &features.enabled,
+                Some(features.default_features),
+            );
+        }
+        std::fs::write(&path, doc.to_string())?;
+        Ok(())
+    }
 }
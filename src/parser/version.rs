@@ -0,0 +1,442 @@
+//! Versions and version requirements shared by all the package managers
+//!
+//! Every package manager has its own syntax for versions and requirements.
+//! The parsers translate them into the types in here so that the logic for
+//! figuring out upgrades stays the same irrespective of the package manager.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Deref;
+
+use semver::Identifier;
+
+/// A single version of a package
+///
+/// Versions are ordered using their semver equivalent. The version as
+/// written by the package manager is kept around so that it can be
+/// displayed and written back to the config file as is.
+#[derive(Debug, Clone)]
+pub struct Version {
+    semver: semver::Version,
+    raw: String,
+}
+
+impl Version {
+    /// Parse a version in semver syntax
+    pub fn parse(version: &str) -> Result<Version, semver::SemVerError> {
+        Ok(Version {
+            semver: semver::Version::parse(version)?,
+            raw: version.to_string(),
+        })
+    }
+
+    /// Create a version from its semver equivalent and the string it was
+    /// read from
+    pub fn new(semver: semver::Version, raw: &str) -> Version {
+        Version {
+            semver,
+            raw: raw.to_string(),
+        }
+    }
+}
+
+impl Deref for Version {
+    type Target = semver::Version;
+
+    fn deref(&self) -> &semver::Version {
+        &self.semver
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+// Build metadata is used by parsers to stash things like extra release
+// numbers or post releases, so it takes part in the ordering here even
+// though semver ignores it.
+impl Ord for Version {
+    fn cmp(&self, other: &Version) -> Ordering {
+        self.semver
+            .cmp(&other.semver)
+            .then_with(|| self.semver.build.cmp(&other.semver.build))
+            .then_with(|| self.raw.cmp(&other.raw))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Version) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Exact,
+    NotEqual,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+}
+
+#[derive(Debug, Clone)]
+pub struct Comparator {
+    pub op: Op,
+    pub version: semver::Version,
+}
+
+impl Comparator {
+    pub fn new(op: Op, version: semver::Version) -> Comparator {
+        Comparator { op, version }
+    }
+
+    /// Build metadata is compared like in the ordering of `Version`
+    pub fn matches(&self, version: &semver::Version) -> bool {
+        let ordering = version
+            .cmp(&self.version)
+            .then_with(|| version.build.cmp(&self.version.build));
+        match self.op {
+            Op::Exact => ordering == Ordering::Equal,
+            Op::NotEqual => ordering != Ordering::Equal,
+            Op::Greater => ordering == Ordering::Greater,
+            Op::GreaterEq => ordering != Ordering::Less,
+            Op::Less => ordering == Ordering::Less,
+            Op::LessEq => ordering != Ordering::Greater,
+        }
+    }
+}
+
+/// A possibly incomplete version like `1`, `1.2` or `1.2.x` as found in
+/// version requirements. Missing and wildcard parts are `None`.
+///
+/// Build metadata only makes sense for complete versions, where parsers
+/// use it for the same things as in their versions.
+#[derive(Debug, Clone, Default)]
+pub struct Partial {
+    pub major: Option<u64>,
+    pub minor: Option<u64>,
+    pub patch: Option<u64>,
+    pub pre: Vec<Identifier>,
+    pub build: Vec<Identifier>,
+}
+
+impl Partial {
+    /// Parse a version in semver syntax allowing for missing parts and `x`,
+    /// `X` or `*` as wildcards. Build metadata is ignored.
+    pub fn parse(version: &str) -> Option<Partial> {
+        let version = version.trim();
+        let version = version.split('+').next()?;
+        let (release, pre) = match version.find('-') {
+            Some(pos) => (&version[..pos], Some(&version[pos + 1..])),
+            None => (version, None),
+        };
+
+        let mut parts = vec![];
+        for part in release.split('.') {
+            match part {
+                "x" | "X" | "*" => parts.push(None),
+                p => parts.push(Some(p.parse::<u64>().ok()?)),
+            }
+        }
+        if parts.is_empty() || parts.len() > 3 {
+            return None;
+        }
+
+        let pre = match pre {
+            Some(p) => p
+                .split('.')
+                .map(parse_identifier)
+                .collect::<Option<Vec<_>>>()?,
+            None => vec![],
+        };
+
+        // Anything after a wildcard is a wildcard too
+        let mut wildcard = false;
+        let mut get = |idx: usize| -> Option<u64> {
+            let part = parts.get(idx).cloned().flatten();
+            if part.is_none() {
+                wildcard = true;
+            }
+            if wildcard {
+                None
+            } else {
+                part
+            }
+        };
+        Some(Partial {
+            major: get(0),
+            minor: get(1),
+            patch: get(2),
+            pre,
+            build: vec![],
+        })
+    }
+
+    /// Create a partial version from release numbers, `[1, 2]` being `1.2`
+    pub fn from_release(release: &[u64], pre: Vec<Identifier>) -> Partial {
+        Partial {
+            major: release.first().cloned(),
+            minor: release.get(1).cloned(),
+            patch: release.get(2).cloned(),
+            pre,
+            build: vec![],
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.major.is_some() && self.minor.is_some() && self.patch.is_some()
+    }
+
+    /// The smallest version described, filling in zeros for missing parts
+    pub fn lower(&self) -> semver::Version {
+        semver::Version {
+            major: self.major.unwrap_or(0),
+            minor: self.minor.unwrap_or(0),
+            patch: self.patch.unwrap_or(0),
+            pre: self.pre.clone(),
+            build: self.build.clone(),
+        }
+    }
+
+    /// The first version after all the versions described by the parts
+    /// which have been specified
+    fn upper(&self) -> Option<semver::Version> {
+        match (self.major, self.minor, self.patch) {
+            (None, _, _) => None,
+            (Some(major), None, _) => Some(semver::Version::new(major + 1, 0, 0)),
+            (Some(major), Some(minor), None) => Some(semver::Version::new(major, minor + 1, 0)),
+            (Some(major), Some(minor), Some(patch)) => {
+                Some(semver::Version::new(major, minor, patch + 1))
+            }
+        }
+    }
+
+    /// `1.2.3` or the x-range `1.2`
+    pub fn exact(&self) -> Vec<Comparator> {
+        if self.is_complete() {
+            return vec![Comparator::new(Op::Exact, self.lower())];
+        }
+        self.range(self.upper())
+    }
+
+    /// `^1.2.3`: changes which do not modify the left-most non-zero part
+    pub fn caret(&self) -> Vec<Comparator> {
+        let upper = match (self.major, self.minor, self.patch) {
+            (None, _, _) => None,
+            (Some(major), None, _) => Some(semver::Version::new(major + 1, 0, 0)),
+            (Some(0), Some(0), Some(patch)) => Some(semver::Version::new(0, 0, patch + 1)),
+            (Some(0), Some(minor), _) => Some(semver::Version::new(0, minor + 1, 0)),
+            (Some(major), _, _) => Some(semver::Version::new(major + 1, 0, 0)),
+        };
+        self.range(upper)
+    }
+
+    /// `~1.2.3`: patch level changes if minor is specified, minor level
+    /// changes otherwise
+    pub fn tilde(&self) -> Vec<Comparator> {
+        let upper = match (self.major, self.minor) {
+            (None, _) => None,
+            (Some(major), None) => Some(semver::Version::new(major + 1, 0, 0)),
+            (Some(major), Some(minor)) => Some(semver::Version::new(major, minor + 1, 0)),
+        };
+        self.range(upper)
+    }
+
+    /// `~> 1.2` in Ruby, `~=1.2` in Python and `~1.2` in Composer: only the
+    /// last specified part is allowed to change
+    pub fn pessimistic(&self) -> Vec<Comparator> {
+        let upper = match (self.major, self.minor, self.patch) {
+            (None, _, _) => None,
+            (Some(major), _, None) => Some(semver::Version::new(major + 1, 0, 0)),
+            (Some(major), minor, Some(_)) => {
+                Some(semver::Version::new(major, minor.unwrap_or(0) + 1, 0))
+            }
+        };
+        self.range(upper)
+    }
+
+    pub fn greater(&self) -> Vec<Comparator> {
+        if self.is_complete() {
+            return vec![Comparator::new(Op::Greater, self.lower())];
+        }
+        match self.upper() {
+            Some(upper) => vec![Comparator::new(Op::GreaterEq, upper)],
+            None => nothing(),
+        }
+    }
+
+    pub fn greater_eq(&self) -> Vec<Comparator> {
+        match self.major {
+            Some(_) => vec![Comparator::new(Op::GreaterEq, self.lower())],
+            None => vec![],
+        }
+    }
+
+    pub fn less(&self) -> Vec<Comparator> {
+        match self.major {
+            Some(_) => vec![Comparator::new(Op::Less, self.lower())],
+            None => nothing(),
+        }
+    }
+
+    pub fn less_eq(&self) -> Vec<Comparator> {
+        if self.is_complete() {
+            return vec![Comparator::new(Op::LessEq, self.lower())];
+        }
+        match self.upper() {
+            Some(upper) => vec![Comparator::new(Op::Less, upper)],
+            None => vec![],
+        }
+    }
+
+    /// Only complete versions can be excluded, anything else is ignored
+    pub fn not_equal(&self) -> Vec<Comparator> {
+        if self.is_complete() {
+            vec![Comparator::new(Op::NotEqual, self.lower())]
+        } else {
+            vec![]
+        }
+    }
+
+    fn range(&self, upper: Option<semver::Version>) -> Vec<Comparator> {
+        match upper {
+            Some(upper) => vec![
+                Comparator::new(Op::GreaterEq, self.lower()),
+                Comparator::new(Op::Less, upper),
+            ],
+            None => vec![],
+        }
+    }
+}
+
+fn nothing() -> Vec<Comparator> {
+    vec![Comparator::new(Op::Less, semver::Version::new(0, 0, 0))]
+}
+
+pub fn parse_identifier(identifier: &str) -> Option<Identifier> {
+    if identifier.is_empty() {
+        return None;
+    }
+    match identifier.parse::<u64>() {
+        Ok(n) => Some(Identifier::Numeric(n)),
+        Err(_) => Some(Identifier::AlphaNumeric(identifier.to_string())),
+    }
+}
+
+/// A version requirement
+///
+/// It is made up of a list of alternatives, any of which can match. Each
+/// alternative is a list of comparators which all have to match.
+#[derive(Debug, Clone)]
+pub struct VersionReq {
+    raw: String,
+    alternatives: Vec<Vec<Comparator>>,
+}
+
+impl VersionReq {
+    pub fn new(raw: &str, alternatives: Vec<Vec<Comparator>>) -> VersionReq {
+        VersionReq {
+            raw: raw.to_string(),
+            alternatives,
+        }
+    }
+
+    /// Parse a requirement in the syntax used by Cargo, like `^1.2`,
+    /// `>= 1.2, < 1.5` or `1.*`
+    pub fn parse(requirement: &str) -> Result<VersionReq, String> {
+        let mut comparators = vec![];
+        for term in requirement.split(',') {
+            let term = term.trim();
+            let (op, version) = split_operator(term);
+            let partial =
+                Partial::parse(version).ok_or_else(|| format!("Invalid version in {}", term))?;
+            let wildcard = version.contains(['*', 'x', 'X']);
+            comparators.extend(match op {
+                "" if wildcard => partial.exact(),
+                "" => partial.caret(),
+                "^" => partial.caret(),
+                "~" => partial.tilde(),
+                "=" => partial.exact(),
+                ">" => partial.greater(),
+                ">=" => partial.greater_eq(),
+                "<" => partial.less(),
+                "<=" => partial.less_eq(),
+                _ => return Err(format!("Invalid operator in {}", term)),
+            });
+        }
+        Ok(VersionReq::new(requirement, vec![comparators]))
+    }
+
+    /// Check if the version satisfies the requirement
+    ///
+    /// Pre-releases only match if one of the comparators explicitly
+    /// mentions a pre-release of the same major, minor and patch version.
+    pub fn matches(&self, version: &Version) -> bool {
+        self.alternatives.iter().any(|comparators| {
+            comparators.iter().all(|c| c.matches(version))
+                && (version.pre.is_empty()
+                    || comparators.iter().any(|c| {
+                        !c.version.pre.is_empty()
+                            && c.version.major == version.major
+                            && c.version.minor == version.minor
+                            && c.version.patch == version.patch
+                    }))
+        })
+    }
+
+    /// The oldest version which can satisfy the requirement, ignoring
+    /// versions it excludes with `!=`
+    pub fn lower_bound(&self) -> Option<semver::Version> {
+        self.alternatives
+            .iter()
+            .map(|comparators| {
+                comparators
+                    .iter()
+                    .filter_map(|c| match c.op {
+                        Op::Exact | Op::GreaterEq => Some(c.version.clone()),
+                        Op::Greater => {
+                            let mut next = c.version.clone();
+                            next.increment_patch();
+                            Some(next)
+                        }
+                        _ => None,
+                    })
+                    .max()
+                    .unwrap_or_else(|| semver::Version::new(0, 0, 0))
+            })
+            .min()
+    }
+
+    /// Check if the requirement explicitly mentions a pre-release
+    pub fn has_prerelease(&self) -> bool {
+        self.alternatives
+            .iter()
+            .flatten()
+            .any(|c| !c.version.pre.is_empty())
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+/// Split an operator like `>=` or `^` from the start of a requirement term
+pub fn split_operator(term: &str) -> (&str, &str) {
+    let end = term
+        .find(|c: char| !"<>=^~!".contains(c))
+        .unwrap_or(term.len());
+    (&term[..end], term[end..].trim())
+}
@@ -1,6 +1,6 @@
 use std::collections::hash_map::HashMap;
 
-use crate::events::{StatefulList, TabsState};
+use crate::events::{StatefulList, TabItem, TabsState};
 use termion::event::Key;
 use tui::backend::Backend;
 use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
@@ -10,7 +10,10 @@ use tui::widgets::{Block, BorderType, Borders, Clear, List, Paragraph, Tabs, Tex
 use std::process::Command;
 use tui::terminal::Frame;
 
-use crate::parser::{stringify, ParserKind, Project, SearchDep, UpgradeType};
+use crate::parser::{
+    stringify, DependencyFeatures, Duplicate, LicenseStatus, LockedPackage, ParserKind, Project,
+    SearchDep, UpgradeType,
+};
 
 pub struct AppState {
     tab: usize,
@@ -25,11 +28,26 @@ pub struct InstallCandidate {
     pub kind: String,
 }
 
+/// Value of the tab listing packages locked at more than one version,
+/// which no group of dependencies can be named as
+const DUPLICATES_TAB: &str = "";
+
+/// A row in the tree of the packages a dependency pulls in
+#[derive(Debug, Clone)]
+pub struct TreeNode {
+    depth: usize,
+    package: LockedPackage,
+    expanded: bool,
+}
+
 #[derive(Debug)]
 pub enum PopupKind {
     Message,
     Help,
     Versions,
+    Features,
+    Tree,
+    Why,
     SearchInput,
     SearchList,
     FilterInput,
@@ -43,6 +61,14 @@ pub struct App {
     items: Vec<String>,
     items_to_render: StatefulList<String>,
     versions: StatefulList<String>,
+    features: StatefulList<Option<String>>, // None is default-features
+    pending_features: Option<DependencyFeatures>,
+    tree: StatefulList<TreeNode>,
+    fetched_packages: Vec<String>, // packages in the tree we tried fetching
+    why: StatefulList<String>,
+    why_package: String,
+    why_from_tree: bool,
+    duplicates: StatefulList<Duplicate>,
     pub popup: PopupKind,
     help_content_pos: u16,
     message: Option<String>,
@@ -51,18 +77,36 @@ pub struct App {
     pub search_result: StatefulList<SearchDep>,
     updated_items: HashMap<String, String>,
     show_uptodate: bool,
+    show_optional: bool,
+    show_prereleases: bool,
+    only_license_violations: bool,
+    pending_install: Option<InstallCandidate>, // waiting for a license check
 }
 
 impl App {
     pub fn new(project: Project, kind: ParserKind, folder: &str) -> App {
-        let dep_kinds = project.get_groups();
+        let mut dep_kinds = project.get_groups();
         let dep_names = project.get_deps_in_group(&dep_kinds[0].value);
+        let mut duplicates = StatefulList::with_items(project.get_duplicates());
+        if !duplicates.items.is_empty() {
+            duplicates.first();
+            dep_kinds.push(TabItem {
+                value: DUPLICATES_TAB.to_string(),
+                label: "Duplicates".to_string(),
+            });
+        }
         let mut dep_versions = vec![];
         if !dep_names.is_empty() {
-            if let Some(dep) = project.get_dep_versions(&dep_names[0]) {
+            if let Some(dep) = project.get_dep_versions(&dep_kinds[0].value, &dep_names[0]) {
                 dep_versions = dep.iter().map(|x| x.to_string()).collect();
             }
         }
+        // A broken license policy is the only thing worth stopping for
+        let message = project.get_license_policy_error().map(|x| x.to_string());
+        let popup = match message {
+            Some(_) => PopupKind::Message,
+            None => PopupKind::None,
+        };
         App {
             folder: folder.to_string(),
             kind,
@@ -71,36 +115,78 @@ impl App {
             items: dep_names.clone(),
             items_to_render: StatefulList::with_items(dep_names),
             versions: StatefulList::with_items(dep_versions),
-            message: None,
-            popup: PopupKind::None,
+            features: StatefulList::with_items(vec![]),
+            pending_features: None,
+            tree: StatefulList::with_items(vec![]),
+            fetched_packages: vec![],
+            why: StatefulList::with_items(vec![]),
+            why_package: "".to_string(),
+            why_from_tree: false,
+            duplicates,
+            message,
+            popup,
             help_content_pos: 0,
             search_result: StatefulList::with_items(vec![]),
             search_string: "".to_string(),
             filter_string: "".to_string(),
             updated_items: HashMap::new(),
             show_uptodate: true,
+            show_optional: true,
+            show_prereleases: false,
+            only_license_violations: false,
+            pending_install: None,
         }
     }
 
     fn get_current_version_strings(&self) -> Vec<String> {
         let current_dep = self.get_current_dep_name();
-        match current_dep {
-            Some(dep) => match self.project.get_dep_versions(&dep) {
-                Some(v) => v.clone().into_iter().map(|x| x.to_string()).collect(),
+        let current_tab = self.get_current_group_name();
+        match (current_dep, current_tab) {
+            (Some(dep), Some(tab)) => match self.project.get_dep_versions(&tab, &dep) {
+                Some(v) => {
+                    // The version in use is kept even if it is a pre-release
+                    let current = self.project.get_current_version(&tab, &dep);
+                    v.iter()
+                        .filter(|x| {
+                            x.pre.is_empty() || self.shows_prereleases() || Some(*x) == current
+                        })
+                        .map(|x| x.to_string())
+                        .collect()
+                }
                 None => vec![],
             },
             _ => vec![],
         }
     }
 
+    /// Pre-releases are listed if asked for or if the requirement of the
+    /// dependency targets one
+    fn shows_prereleases(&self) -> bool {
+        if self.show_prereleases {
+            return true;
+        }
+        match (self.get_current_group_name(), self.get_current_dep_name()) {
+            (Some(tab), Some(dep)) => self.project.allows_prerelease(&tab, &dep),
+            _ => false,
+        }
+    }
+
+    pub fn toggle_show_prereleases(&mut self) {
+        self.show_prereleases = !self.show_prereleases;
+        let dep_versions = self.get_current_version_strings();
+        self.versions = StatefulList::with_items(dep_versions);
+        self.versions.state.select(self.get_current_version_index());
+    }
+
     pub fn get_selected_version(&self) -> Option<String> {
         Some(self.versions.get_item()?)
     }
 
     pub fn open_homepage(&mut self) {
         let current_dep = self.get_current_dep_name();
-        if let Some(dep) = current_dep {
-            let homepage = self.project.get_homepage(&dep);
+        let current_tab = self.get_current_group_name();
+        if let (Some(dep), Some(tab)) = (current_dep, current_tab) {
+            let homepage = self.project.get_homepage(&tab, &dep);
             if let Some(hp) = homepage {
                 Command::new("open")
                     .arg(hp)
@@ -112,8 +198,9 @@ impl App {
 
     pub fn open_repository(&mut self) {
         let current_dep = self.get_current_dep_name();
-        if let Some(dep) = current_dep {
-            let repository = self.project.get_repository(&dep);
+        let current_tab = self.get_current_group_name();
+        if let (Some(dep), Some(tab)) = (current_dep, current_tab) {
+            let repository = self.project.get_repository(&tab, &dep);
             if let Some(rp) = repository {
                 Command::new("open")
                     .arg(rp)
@@ -130,6 +217,15 @@ impl App {
             PopupKind::FilterInput => PopupKind::None,
             PopupKind::Help => PopupKind::None,
             PopupKind::Versions => PopupKind::None,
+            PopupKind::Features => PopupKind::None,
+            PopupKind::Tree => PopupKind::None,
+            PopupKind::Why => {
+                if self.why_from_tree {
+                    PopupKind::Tree
+                } else {
+                    PopupKind::None
+                }
+            }
             PopupKind::Message => {
                 if !self.search_string.is_empty() {
                     PopupKind::SearchInput
@@ -142,10 +238,10 @@ impl App {
     }
     pub fn toggle_versions_menu(&mut self) {
         if let PopupKind::None = self.popup {
-            if !self
-                .project
-                .is_versions_available(&self.get_current_dep_name().unwrap())
-            {
+            if !self.project.is_versions_available(
+                &self.get_current_group_name().unwrap(),
+                &self.get_current_dep_name().unwrap(),
+            ) {
                 self.message = Some("No versions available".to_string());
                 self.popup = PopupKind::Message;
                 return;
@@ -153,6 +249,198 @@ impl App {
             self.popup = PopupKind::Versions;
         }
     }
+    pub fn toggle_features_menu(&mut self) {
+        match self.popup {
+            PopupKind::None => {
+                let current_tab = self.get_current_group_name().unwrap();
+                let current_dep = match self.get_current_dep_name() {
+                    Some(d) => d,
+                    None => return,
+                };
+                let features = match self.project.get_features(&current_tab, &current_dep) {
+                    Some(f) => f.clone(),
+                    None => {
+                        self.message = Some("No features available".to_string());
+                        self.popup = PopupKind::Message;
+                        return;
+                    }
+                };
+                let mut names: Vec<String> = self
+                    .project
+                    .get_available_features(&current_tab, &current_dep)
+                    .map(|x| x.keys().filter(|k| *k != "default").cloned().collect())
+                    .unwrap_or_default();
+                // Features which are not in the version in use
+                for feature in &features.enabled {
+                    if !names.contains(feature) {
+                        names.push(feature.to_string());
+                    }
+                }
+                let mut items = vec![None];
+                items.extend(names.into_iter().map(Some));
+                self.features = StatefulList::with_items(items);
+                self.features.next();
+                self.pending_features = Some(features);
+                self.popup = PopupKind::Features;
+            }
+            PopupKind::Features => {
+                self.popup = PopupKind::None;
+            }
+            _ => {}
+        }
+    }
+    pub fn toggle_feature(&mut self) {
+        if let Some(pending) = &mut self.pending_features {
+            match self.features.get_item() {
+                Some(Some(feature)) => {
+                    if pending.enabled.contains(&feature) {
+                        pending.enabled.retain(|x| x != &feature);
+                    } else {
+                        pending.enabled.push(feature);
+                    }
+                }
+                Some(None) => pending.default_features = !pending.default_features,
+                None => {}
+            }
+        }
+    }
+    pub fn toggle_tree_menu(&mut self) {
+        match self.popup {
+            PopupKind::None => {
+                let current_tab = self.get_current_group_name().unwrap();
+                let current_dep = match self.get_current_dep_name() {
+                    Some(d) => d,
+                    None => return,
+                };
+                let package = match self.project.get_locked_package(&current_tab, &current_dep) {
+                    Some(p) => p,
+                    None => {
+                        self.message = Some("Not in the lockfile".to_string());
+                        self.popup = PopupKind::Message;
+                        return;
+                    }
+                };
+                self.tree = StatefulList::with_items(vec![TreeNode {
+                    depth: 0,
+                    package,
+                    expanded: false,
+                }]);
+                self.tree.first();
+                self.toggle_tree_node();
+                self.popup = PopupKind::Tree;
+            }
+            PopupKind::Tree => {
+                self.popup = PopupKind::None;
+            }
+            _ => {}
+        }
+    }
+    /// Expand or collapse the selected package in the tree
+    pub fn toggle_tree_node(&mut self) {
+        let index = match self.tree.state.selected() {
+            Some(i) => i,
+            None => return,
+        };
+        let node = self.tree.items[index].clone();
+        if node.expanded {
+            let end = self.tree.items[index + 1..]
+                .iter()
+                .position(|x| x.depth <= node.depth)
+                .map_or(self.tree.items.len(), |x| x + index + 1);
+            self.tree.items.drain(index + 1..end);
+            self.tree.items[index].expanded = false;
+            return;
+        }
+        // Packages depending on each other would go on forever
+        let mut depth = node.depth;
+        for item in self.tree.items[..index].iter().rev() {
+            if item.depth < depth {
+                if item.package == node.package {
+                    return;
+                }
+                depth = item.depth;
+            }
+        }
+        let children = match self.project.get_locked_dependencies(&node.package) {
+            Some(c) if !c.is_empty() => c,
+            _ => return,
+        };
+        let rows: Vec<TreeNode> = children
+            .iter()
+            .map(|x| TreeNode {
+                depth: node.depth + 1,
+                package: x.clone(),
+                expanded: false,
+            })
+            .collect();
+        self.tree.items.splice(index + 1..index + 1, rows);
+        self.tree.items[index].expanded = true;
+    }
+    /// Show the ways the selected package ends up in the project, either
+    /// a dependency from the list or a package from the tree
+    pub fn toggle_why_menu(&mut self) {
+        let name = match self.popup {
+            PopupKind::None if self.shows_duplicates() => {
+                self.duplicates.get_item().map(|x| x.name)
+            }
+            PopupKind::None => {
+                let current_tab = self.get_current_group_name().unwrap();
+                let current_dep = match self.get_current_dep_name() {
+                    Some(d) => d,
+                    None => return,
+                };
+                self.project
+                    .get_locked_package(&current_tab, &current_dep)
+                    .map(|x| x.name)
+            }
+            PopupKind::Tree => self.tree.get_item().map(|x| x.package.name),
+            PopupKind::Why => {
+                self.unwrap_popup();
+                return;
+            }
+            _ => return,
+        };
+        let paths = name
+            .as_ref()
+            .map(|x| self.project.get_dependency_paths(x))
+            .unwrap_or_default();
+        if paths.is_empty() {
+            self.message = Some("Not in the dependency graph".to_string());
+            self.popup = PopupKind::Message;
+            return;
+        }
+        self.why_from_tree = matches!(self.popup, PopupKind::Tree);
+        self.why_package = name.unwrap_or_default();
+        self.why = StatefulList::with_items(paths.iter().map(|x| x.to_string()).collect());
+        self.why.first();
+        self.popup = PopupKind::Why;
+    }
+    /// Packages in the tree whose versions are yet to be fetched
+    fn get_missing_tree_packages(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .tree
+            .items
+            .iter()
+            .map(|x| x.package.name.to_string())
+            .filter(|x| !self.project.has_package_metadata(x) && !self.fetched_packages.contains(x))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+    /// Fetch versions of the packages in the tree so that their upgrades
+    /// can be shown. Returns false if there was nothing to fetch.
+    pub async fn fetch_tree_metadata(&mut self) -> bool {
+        let names = self.get_missing_tree_packages();
+        if names.is_empty() {
+            return false;
+        }
+        self.project
+            .fetch_package_metadata(&self.kind, &names)
+            .await;
+        self.fetched_packages.extend(names);
+        true
+    }
     pub fn toggle_help_menu(&mut self) {
         match self.popup {
             PopupKind::None => {
@@ -182,6 +470,16 @@ impl App {
                         None => true,
                     }
                 })
+                .filter(|x| match &self.get_current_group_name() {
+                    Some(ct) => self.show_optional || self.project.is_default_build(ct, x),
+                    None => true,
+                })
+                .filter(|x| match &self.get_current_group_name() {
+                    Some(ct) => {
+                        !self.only_license_violations || self.project.violates_license_policy(ct, x)
+                    }
+                    None => true,
+                })
                 .filter(|x| x.contains(&self.filter_string))
                 .collect(),
         );
@@ -217,6 +515,34 @@ impl App {
         self.update_items_to_render();
     }
 
+    pub fn toggle_license_violations(&mut self) {
+        if let Some(e) = self
+            .project
+            .get_license_policy_error()
+            .map(|x| x.to_string())
+        {
+            self.set_message(&e);
+            return;
+        }
+        if !self.project.has_license_policy() {
+            self.set_message("No license policy in .depman.toml");
+            return;
+        }
+        self.only_license_violations = !self.only_license_violations;
+        self.update_items_to_render();
+    }
+
+    pub fn toggle_show_optional(&mut self) {
+        self.show_optional = !self.show_optional;
+        self.update_items_to_render();
+    }
+
+    /// Check if the tab with the packages locked at more than one version
+    /// is the one open
+    pub fn shows_duplicates(&self) -> bool {
+        self.tabs.items[self.tabs.index].value == DUPLICATES_TAB
+    }
+
     pub fn _get_current_tab_name(&self) -> String {
         self.tabs.items[self.tabs.index].value.to_string()
     }
@@ -225,6 +551,15 @@ impl App {
         if let PopupKind::Versions = self.popup {
             self.versions.first();
             self.versions.next()
+        } else if let PopupKind::Features = self.popup {
+            self.features.first();
+            self.features.next()
+        } else if let PopupKind::Tree = self.popup {
+            self.tree.first();
+        } else if let PopupKind::Why = self.popup {
+            self.why.first();
+        } else if self.shows_duplicates() {
+            self.duplicates.first();
         } else {
             self.items_to_render.first();
             let dep_versions = self.get_current_version_strings();
@@ -236,6 +571,14 @@ impl App {
     pub fn bottom(&mut self) {
         if let PopupKind::Versions = self.popup {
             self.versions.last();
+        } else if let PopupKind::Features = self.popup {
+            self.features.last();
+        } else if let PopupKind::Tree = self.popup {
+            self.tree.last();
+        } else if let PopupKind::Why = self.popup {
+            self.why.last();
+        } else if self.shows_duplicates() {
+            self.duplicates.last();
         } else {
             self.items_to_render.last();
             let dep_versions = self.get_current_version_strings();
@@ -247,8 +590,12 @@ impl App {
     pub fn next(&mut self) {
         match self.popup {
             PopupKind::Versions => self.versions.next(),
+            PopupKind::Features => self.features.next(),
+            PopupKind::Tree => self.tree.next(),
+            PopupKind::Why => self.why.next(),
             PopupKind::Help => self.help_content_pos += 1,
             PopupKind::SearchList => self.search_result.next(),
+            _ if self.shows_duplicates() => self.duplicates.next(),
             _ => {
                 self.items_to_render.next();
                 let dep_versions = self.get_current_version_strings();
@@ -261,12 +608,16 @@ impl App {
     pub fn previous(&mut self) {
         match self.popup {
             PopupKind::Versions => self.versions.previous(),
+            PopupKind::Features => self.features.previous(),
+            PopupKind::Tree => self.tree.previous(),
+            PopupKind::Why => self.why.previous(),
             PopupKind::Help => {
                 if self.help_content_pos > 0 {
                     self.help_content_pos -= 1;
                 }
             }
             PopupKind::SearchList => self.search_result.previous(),
+            _ if self.shows_duplicates() => self.duplicates.previous(),
             _ => {
                 self.items_to_render.previous();
                 let dep_versions = self.get_current_version_strings();
@@ -304,7 +655,8 @@ impl App {
     }
 
     pub fn set_state(&mut self, state: AppState) {
-        self.tabs.index = state.tab;
+        // The duplicates tab goes away once there are none left
+        self.tabs.index = state.tab.min(self.tabs.items.len() - 1);
         let dep_names = self
             .project
             .get_deps_in_group(&self.tabs.items[self.tabs.index].value);
@@ -352,15 +704,59 @@ impl App {
         }
     }
 
-    pub fn install_dep(&mut self) -> bool {
-        let install_candidate = self.get_install_candidate();
-        if let Some(ic) = install_candidate {
-            self.updated_items
-                .insert(ic.name.to_string(), ic.version.to_string());
-            self.project.install_dep(&self.kind, &self.folder, ic);
-            true
-        } else {
-            false
+    /// Install the selected version, returning the message to show
+    pub fn install_dep(&mut self) -> Result<String, String> {
+        match self.get_install_candidate() {
+            Some(ic) => self.install_candidate(ic),
+            None => Err("Update failed.".to_string()),
+        }
+    }
+
+    fn install_candidate(&mut self, ic: InstallCandidate) -> Result<String, String> {
+        let (name, version, group) = (
+            ic.name.to_string(),
+            ic.version.to_string(),
+            ic.kind.to_string(),
+        );
+        self.project.install_dep(&self.kind, &self.folder, ic)?;
+        self.updated_items.insert(name, version);
+        Ok(self
+            .kind
+            .install_notice(&self.folder, &group)
+            .unwrap_or_else(|| "Dependency updated!".to_string()))
+    }
+
+    /// Hold on to the package about to be installed so that its license
+    /// can be checked first. Returns false if there is no policy to check
+    /// against.
+    pub fn queue_license_check(&mut self) -> bool {
+        if !self.project.has_license_policy() {
+            return false;
+        }
+        self.pending_install = self.get_install_candidate();
+        self.pending_install.is_some()
+    }
+
+    /// Install the package held back by `queue_license_check` if the
+    /// license policy allows it
+    pub async fn install_checked_dep(&mut self) -> Result<String, String> {
+        let ic = match self.pending_install.take() {
+            Some(ic) => ic,
+            None => return Err("Update failed.".to_string()),
+        };
+        self.project.check_license(&self.kind, &ic).await?;
+        self.install_candidate(ic)
+    }
+
+    pub fn save_features(&mut self) -> bool {
+        let current_tab = self.get_current_group_name().unwrap();
+        let current_dep = self.get_current_dep_name();
+        match (&current_dep, &self.pending_features) {
+            (Some(cd), Some(pending)) => {
+                self.project
+                    .update_features(&self.kind, &self.folder, &current_tab, cd, pending)
+            }
+            _ => false,
         }
     }
 
@@ -482,7 +878,10 @@ impl App {
                     .alignment(Alignment::Left)
                     .scroll(self.help_content_pos)
                     .wrap(true);
-                let area = centered_rect_absolute(50, 3, f.size());
+                // Longer messages wrap onto more lines
+                let lines = message.len() as u16 / 40 + 1;
+                let height = (lines + 2).min(f.size().height);
+                let area = centered_rect_absolute(50, height, f.size());
                 f.render_widget(Clear, area); //this clears out the background
                 f.render_widget(block, area);
             }
@@ -498,10 +897,16 @@ impl App {
                 ["h/left", "prev tab"],
                 ["l/right", "next tab"],
                 ["v/space", "show version list"],
+                ["P", "toggle showing pre-releases"],
+                ["f", "show feature list, space toggles"],
+                ["t", "show dependency tree, space expands"],
+                ["w", "show why a package is in the project"],
                 ["o", "open homepage"],
                 ["p", "open package repo"],
                 ["/", "search installed packages"],
                 ["U", "toggle showing uptodate packages"],
+                ["O", "toggle showing optional packages"],
+                ["L", "toggle showing only license violations"],
                 ["i", "search and install package"],
                 ["D", "delete package"],
                 ["enter", "update/install package"],
@@ -533,6 +938,21 @@ impl App {
             text.push(Text::styled(" Minor upgrade\n", Style::default()));
             text.push(Text::styled("Red", Style::default().fg(Color::Red)));
             text.push(Text::styled(" Major upgrade\n", Style::default()));
+            text.push(Text::styled("!license", Style::default().fg(Color::Yellow)));
+            text.push(Text::styled(
+                " License not allowed by .depman.toml\n",
+                Style::default(),
+            ));
+            text.push(Text::styled("?license", Style::default().fg(Color::Yellow)));
+            text.push(Text::styled(
+                " License is free text that can't be checked\n",
+                Style::default(),
+            ));
+            text.push(Text::styled("!vulnerable", Style::default().fg(Color::Red)));
+            text.push(Text::styled(
+                " Has security advisories, itself or its dependencies\n",
+                Style::default(),
+            ));
             let block = Paragraph::new(text.iter())
                 .block(
                     Block::default()
@@ -581,26 +1001,44 @@ impl App {
             if let PopupKind::Versions = self.popup {
                 let mut items = vec![];
                 for item in self.versions.items.iter() {
-                    if &stringify(&self.project.get_current_version(&d)) == item
+                    let mut labels_string =
+                        match self.project.get_version_tags(current_tab, &d, item) {
+                            Some(tags) => format!(" [{}]", tags.join(", ")),
+                            None => "".to_string(),
+                        };
+                    let withdrawal = self.project.get_withdrawal(current_tab, &d, item);
+                    if let Some(w) = withdrawal {
+                        labels_string = format!("{} {}", labels_string, w);
+                    }
+                    if &stringify(&self.project.get_patched_version(current_tab, &d)) == item {
+                        labels_string = format!("{} min-patched", labels_string);
+                    }
+                    if &stringify(&self.project.get_current_version(current_tab, &d)) == item
                         && &stringify(&self.project.get_semver_version(&current_tab, &d)) == item
                     {
                         items.push(Text::styled(
-                            format!("{} current&latest-semver", item),
+                            format!("{} current&latest-semver{}", item, labels_string),
                             Style::default().fg(Color::Cyan),
                         ));
-                    } else if &stringify(&self.project.get_current_version(&d)) == item {
+                    } else if &stringify(&self.project.get_current_version(current_tab, &d)) == item
+                    {
                         items.push(Text::styled(
-                            format!("{} current", item),
+                            format!("{} current{}", item, labels_string),
                             Style::default().fg(Color::Cyan),
                         ));
                     } else if &stringify(&self.project.get_semver_version(&current_tab, &d)) == item
                     {
                         items.push(Text::styled(
-                            format!("{} latest-semver", item),
+                            format!("{} latest-semver{}", item, labels_string),
                             Style::default().fg(Color::Green),
                         ));
+                    } else if withdrawal.is_some() {
+                        items.push(Text::styled(
+                            format!("{}{}", item, labels_string),
+                            Style::default().fg(Color::DarkGray),
+                        ));
                     } else {
-                        items.push(Text::raw(item));
+                        items.push(Text::raw(format!("{}{}", item, labels_string)));
                     }
                 }
 
@@ -608,7 +1046,7 @@ impl App {
                 let current_item = self.versions.state.selected();
                 if let Some(ci) = current_item {
                     let item = &self.versions.items[ci];
-                    if &stringify(&self.project.get_current_version(&d)) == item {
+                    if &stringify(&self.project.get_current_version(current_tab, &d)) == item {
                         color = Color::Cyan;
                     } else if &stringify(&self.project.get_semver_version(&current_tab, &d)) == item
                     {
@@ -616,10 +1054,15 @@ impl App {
                     }
                 }
 
+                let title = if self.shows_prereleases() {
+                    "Versions (with pre-releases)"
+                } else {
+                    "Versions"
+                };
                 let block = List::new(items.into_iter())
                     .block(
                         Block::default()
-                            .title("Versions")
+                            .title(title)
                             .borders(Borders::ALL)
                             .border_type(BorderType::Rounded)
                             .border_style(Style::default().fg(Color::Red)),
@@ -635,10 +1078,152 @@ impl App {
         }
     }
 
+    pub fn render_features_selector<B: Backend>(&mut self, f: &mut Frame<B>) {
+        if let PopupKind::Features = self.popup {
+            let current_tab = self.get_current_group_name().unwrap();
+            let current_dep = self.get_current_dep_name().unwrap();
+            let defaults = self
+                .project
+                .get_available_features(&current_tab, &current_dep)
+                .and_then(|x| x.get("default"))
+                .cloned()
+                .unwrap_or_default();
+            let pending = match &self.pending_features {
+                Some(p) => p,
+                None => return,
+            };
+            let mut items = vec![];
+            for item in self.features.items.iter() {
+                let (name, enabled) = match item {
+                    Some(feature) => (feature.as_str(), pending.enabled.contains(feature)),
+                    None => ("default-features", pending.default_features),
+                };
+                let mark = if enabled { "x" } else { " " };
+                let style = if enabled {
+                    Style::default().fg(Color::Green)
+                } else {
+                    Style::default()
+                };
+                let label = match item {
+                    Some(feature) if defaults.contains(feature) => {
+                        format!("[{}] {} (default)", mark, name)
+                    }
+                    _ => format!("[{}] {}", mark, name),
+                };
+                items.push(Text::styled(label, style));
+            }
+
+            let block = List::new(items.into_iter())
+                .block(
+                    Block::default()
+                        .title("Features")
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Rounded)
+                        .border_style(Style::default().fg(Color::Red)),
+                )
+                .style(Style::default())
+                .highlight_style(Style::default().fg(Color::Cyan))
+                .highlight_symbol("■ "); // ║ ▓ ■
+
+            let area = centered_rect(50, 50, f.size());
+            f.render_widget(Clear, area); //this clears out the background
+            f.render_stateful_widget(block, area, &mut self.features.state);
+        }
+    }
+
+    pub fn render_dependency_tree<B: Backend>(&mut self, f: &mut Frame<B>) {
+        if let PopupKind::Tree = self.popup {
+            let mut items = vec![];
+            for node in self.tree.items.iter() {
+                let marker = match self.project.get_locked_dependencies(&node.package) {
+                    Some(c) if !c.is_empty() && node.expanded => "-",
+                    Some(c) if !c.is_empty() => "+",
+                    _ => " ",
+                };
+                let upgrade_type = self.project.get_package_upgrade_type(&node.package);
+                let breaking_changes_string = match upgrade_type {
+                    UpgradeType::Breaking => " + ",
+                    _ => "",
+                };
+                let vulnerable = !self
+                    .project
+                    .get_package_advisories(&node.package)
+                    .is_empty();
+                items.push(Text::styled(
+                    format!(
+                        "{}{} {} ({} > {}){}{}",
+                        "  ".repeat(node.depth),
+                        marker,
+                        node.package.name,
+                        node.package.version,
+                        stringify(&self.project.get_package_semver_version(&node.package)),
+                        breaking_changes_string,
+                        if vulnerable { " !vulnerable" } else { "" }
+                    ),
+                    if vulnerable {
+                        Style::default().fg(Color::Red)
+                    } else {
+                        Style::default().fg(get_version_color(upgrade_type))
+                    },
+                ));
+            }
+
+            let color = match self.tree.get_item() {
+                Some(node) => {
+                    get_version_color(self.project.get_package_upgrade_type(&node.package))
+                }
+                None => Color::White,
+            };
+            let title = if self.get_missing_tree_packages().is_empty() {
+                "Dependency Tree"
+            } else {
+                "Dependency Tree (fetching versions...)"
+            };
+            let block = List::new(items.into_iter())
+                .block(
+                    Block::default()
+                        .title(title)
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Rounded)
+                        .border_style(Style::default().fg(Color::Red)),
+                )
+                .style(Style::default())
+                .highlight_style(Style::default().fg(color))
+                .highlight_symbol("■ "); // ║ ▓ ■
+
+            let area = centered_rect(50, 50, f.size());
+            f.render_widget(Clear, area); //this clears out the background
+            f.render_stateful_widget(block, area, &mut self.tree.state);
+        }
+    }
+
+    pub fn render_why_list<B: Backend>(&mut self, f: &mut Frame<B>) {
+        if let PopupKind::Why = self.popup {
+            let items = self.why.items.iter().map(Text::raw);
+            let title = format!("Why {}", self.why_package);
+            let block = List::new(items)
+                .block(
+                    Block::default()
+                        .title(&title)
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Rounded)
+                        .border_style(Style::default().fg(Color::Red)),
+                )
+                .style(Style::default())
+                .highlight_style(Style::default().fg(Color::Cyan))
+                .highlight_symbol("■ "); // ║ ▓ ■
+
+            let area = centered_rect(80, 50, f.size());
+            f.render_widget(Clear, area); //this clears out the background
+            f.render_stateful_widget(block, area, &mut self.why.state);
+        }
+    }
+
     pub fn get_current_version_index(&self) -> Option<usize> {
+        let current_tab = self.get_current_group_name()?;
         if let Some(d) = &self.get_current_dep_name() {
             for (i, item) in self.versions.items.iter().enumerate() {
-                if &stringify(&self.project.get_current_version(&d)) == item {
+                if &stringify(&self.project.get_current_version(&current_tab, d)) == item {
                     return Some(i);
                 }
             }
@@ -653,16 +1238,16 @@ impl App {
             .clone()
             .into_iter()
             .map(|i| {
-                format!(
-                    "{}({})",
-                    i.label,
+                let count = if i.value == DUPLICATES_TAB {
+                    self.duplicates.items.len()
+                } else {
                     self.project
                         .get_deps_in_group(&i.value)
                         .iter()
                         .filter(|x| x.contains(&self.filter_string))
-                        .collect::<Vec<_>>()
-                        .len()
-                )
+                        .count()
+                };
+                format!("{}({})", i.label, count)
             })
             .collect::<Vec<String>>();
         let tabs = Tabs::default()
@@ -677,12 +1262,21 @@ impl App {
     }
 
     pub fn render_dependency_info<B: Backend>(&mut self, f: &mut Frame<B>, chunk: Rect) {
+        if self.shows_duplicates() {
+            self.render_duplicate_info(f, chunk);
+            return;
+        }
         let current_tab = &self.get_current_group_name().unwrap();
         let dep = self.get_current_dep_name();
         if let Some(d) = dep {
-            let text = [
+            let mut text = vec![
                 Text::styled("Name", Style::default().fg(Color::Red)),
                 Text::raw(format!(" {}\n", d)),
+                Text::styled("Source", Style::default().fg(Color::Red)),
+                Text::raw(format!(
+                    " {}\n",
+                    stringify(&self.project.get_source(current_tab, &d))
+                )),
                 Text::styled("Specified Version", Style::default().fg(Color::Blue)),
                 Text::raw(format!(
                     " {}\n",
@@ -691,35 +1285,238 @@ impl App {
                 Text::styled("Current Version", Style::default().fg(Color::Blue)),
                 Text::raw(format!(
                     " {}\n",
-                    stringify(&self.project.get_current_version(&d))
+                    stringify(&self.project.get_current_version(current_tab, &d))
                 )),
-                Text::styled("Upgradeable Version", Style::default().fg(Color::Blue)),
+                Text::styled("Locked Versions", Style::default().fg(Color::Blue)),
                 Text::raw(format!(
                     " {}\n",
+                    stringify(&self.project.get_locked_versions(current_tab, &d).map(|x| {
+                        x.iter()
+                            .map(|v| v.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    }))
+                )),
+                Text::styled("Upgradeable Version", Style::default().fg(Color::Blue)),
+                Text::raw(format!(
+                    " {}",
                     stringify(&self.project.get_semver_version(&current_tab, &d))
                 )),
+            ];
+            if let Some(toolchain) = self.project.get_toolchain() {
+                text.extend(vec![
+                    Text::styled(
+                        format!("  Latest Compatible ({})", toolchain),
+                        Style::default().fg(Color::Blue),
+                    ),
+                    Text::raw(format!(
+                        " {}",
+                        stringify(&self.project.get_compatible_version(current_tab, &d))
+                    )),
+                ]);
+            }
+            text.extend(vec![
+                Text::raw("\n"),
                 Text::styled("Latest Version", Style::default().fg(Color::Blue)),
                 Text::raw(format!(
                     " {}\n",
-                    stringify(&self.project.get_latest_version(&d))
+                    stringify(&self.project.get_latest_version(current_tab, &d))
                 )),
+            ]);
+            if let Some(warning) = self.project.get_current_version_warning(current_tab, &d) {
+                text.extend(vec![
+                    Text::styled("Warning", Style::default().fg(Color::Red)),
+                    Text::raw(format!(" {}\n", warning)),
+                ]);
+            }
+            for (package, advisory) in self.project.get_vulnerabilities(current_tab, &d) {
+                let severity = match &advisory.severity {
+                    Some(s) => format!(" [{}]", s.to_lowercase()),
+                    None => "".to_string(),
+                };
+                let aliases = if advisory.aliases.is_empty() {
+                    "".to_string()
+                } else {
+                    format!(" ({})", advisory.aliases.join(", "))
+                };
+                text.extend(vec![
+                    Text::styled("Advisory", Style::default().fg(Color::Red)),
+                    Text::raw(format!(
+                        " {}{}{} in {}: {} {}\n",
+                        advisory.id,
+                        aliases,
+                        severity,
+                        package,
+                        advisory.title,
+                        stringify(&advisory.url)
+                    )),
+                ]);
+            }
+            if let Some(patched) = self.project.get_patched_version(current_tab, &d) {
+                text.extend(vec![
+                    Text::styled("Patched Version", Style::default().fg(Color::Red)),
+                    Text::raw(format!(" {}\n", patched)),
+                ]);
+            }
+            if let Some(features) = self.project.get_features(current_tab, &d) {
+                let available = self
+                    .project
+                    .get_available_features(current_tab, &d)
+                    .map(|x| {
+                        x.keys()
+                            .filter(|k| *k != "default")
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    });
+                text.extend(vec![
+                    Text::styled("Features", Style::default().fg(Color::Blue)),
+                    Text::raw(format!(" {}\n", features.enabled.join(", "))),
+                    Text::styled("Default Features", Style::default().fg(Color::Blue)),
+                    Text::raw(format!(
+                        " {}\n",
+                        if features.default_features {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        }
+                    )),
+                    Text::styled("Available Features", Style::default().fg(Color::Blue)),
+                    Text::raw(format!(" {}\n", stringify(&available))),
+                    Text::styled("Optional", Style::default().fg(Color::Blue)),
+                    Text::raw(if !features.optional {
+                        " no\n".to_string()
+                    } else if features.enabled_by.is_empty() {
+                        " yes, not enabled by any feature\n".to_string()
+                    } else {
+                        format!(" yes, enabled by {}\n", features.enabled_by.join(", "))
+                    }),
+                ]);
+            }
+            text.extend(vec![
                 Text::styled("Author", Style::default().fg(Color::Green)),
-                Text::raw(format!(" {}\n", stringify(&self.project.get_author(&d)))),
+                Text::raw(format!(
+                    " {}\n",
+                    stringify(&self.project.get_author(current_tab, &d))
+                )),
                 Text::styled("Homepage", Style::default().fg(Color::Magenta)),
-                Text::raw(format!(" {}\n", stringify(&self.project.get_homepage(&d)))),
+                Text::raw(format!(
+                    " {}\n",
+                    stringify(&self.project.get_homepage(current_tab, &d))
+                )),
                 Text::styled("Package repo:", Style::default().fg(Color::Magenta)),
                 Text::raw(format!(
                     " {}\n",
-                    stringify(&self.project.get_repository(&d))
+                    stringify(&self.project.get_repository(current_tab, &d))
                 )),
                 Text::styled("License", Style::default().fg(Color::Yellow)),
-                Text::raw(format!(" {}\n", stringify(&self.project.get_license(&d)))),
+                Text::raw(format!(
+                    " {}{}\n",
+                    stringify(&self.project.get_license(current_tab, &d)),
+                    match self.project.get_license_status(current_tab, &d) {
+                        Some(LicenseStatus::Denied) => " (not allowed by the license policy)",
+                        Some(LicenseStatus::Unknown) => " (unknown to the license policy)",
+                        _ => "",
+                    }
+                )),
                 Text::styled("Description", Style::default().fg(Color::Cyan)),
                 Text::raw(format!(
                     " {}\n",
-                    stringify(&self.project.get_description(&d))
+                    stringify(&self.project.get_description(current_tab, &d))
                 )),
+            ]);
+            let block = Paragraph::new(text.iter())
+                .block(
+                    Block::default()
+                        .title("Info")
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Rounded)
+                        .border_style(Style::default().fg(Color::White)),
+                )
+                .style(Style::default())
+                .alignment(Alignment::Left)
+                .wrap(true);
+            f.render_widget(block, chunk);
+        }
+    }
+
+    pub fn render_duplicate_list<B: Backend>(&mut self, f: &mut Frame<B>, chunk: Rect) {
+        let mut items = vec![];
+        for duplicate in self.duplicates.items.iter() {
+            let versions: Vec<&str> = duplicate
+                .copies
+                .iter()
+                .map(|x| x.version.as_str())
+                .collect();
+            // Copies which upgrades could get rid of
+            let fixable = duplicate
+                .copies
+                .iter()
+                .skip(1)
+                .all(|x| !x.dependents.is_empty() && x.upgrades.len() == x.dependents.len());
+            let style = if fixable {
+                Style::default().fg(Color::Green)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            items.push(Text::styled(
+                format!("{} ({})", duplicate.name, versions.join(", ")),
+                style,
+            ));
+        }
+        let block = List::new(items.into_iter())
+            .block(
+                Block::default()
+                    .title("Duplicates")
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(Style::default().fg(Color::White)),
+            )
+            .style(Style::default())
+            .highlight_style(Style::default().fg(Color::Cyan))
+            .highlight_symbol("■ "); // ║ ▓ ■
+        f.render_stateful_widget(block, chunk, &mut self.duplicates.state);
+    }
+
+    pub fn render_duplicate_info<B: Backend>(&mut self, f: &mut Frame<B>, chunk: Rect) {
+        if let Some(duplicate) = self.duplicates.get_item() {
+            let mut text = vec![
+                Text::styled("Name", Style::default().fg(Color::Red)),
+                Text::raw(format!(" {}\n", duplicate.name)),
             ];
+            for (i, copy) in duplicate.copies.iter().enumerate() {
+                let dependents: Vec<String> =
+                    copy.dependents.iter().map(|x| x.to_string()).collect();
+                text.extend(vec![
+                    Text::styled(
+                        format!("Version {}", copy.version),
+                        Style::default().fg(Color::Blue),
+                    ),
+                    Text::raw(if i == 0 { " (newest)\n" } else { "\n" }),
+                    Text::styled("  Pulled in by", Style::default().fg(Color::Green)),
+                    Text::raw(if dependents.is_empty() {
+                        " -\n".to_string()
+                    } else {
+                        format!(" {}\n", dependents.join(", "))
+                    }),
+                ]);
+                if i == 0 {
+                    continue;
+                }
+                let upgrades: Vec<String> = copy
+                    .upgrades
+                    .iter()
+                    .map(|(package, version)| format!("{} to {}", package, version))
+                    .collect();
+                text.extend(vec![
+                    Text::styled("  Upgrades", Style::default().fg(Color::Magenta)),
+                    Text::raw(if upgrades.is_empty() {
+                        " none available\n".to_string()
+                    } else {
+                        format!(" {}\n", upgrades.join(", "))
+                    }),
+                ]);
+            }
             let block = Paragraph::new(text.iter())
                 .block(
                     Block::default()
@@ -744,6 +1541,10 @@ impl App {
     }
 
     pub fn render_dependency_list<B: Backend>(&mut self, f: &mut Frame<B>, chunk: Rect) {
+        if self.shows_duplicates() {
+            self.render_duplicate_list(f, chunk);
+            return;
+        }
         if let Some(dc) = self.get_current_dep_name() {
             let current_tab = &self.get_current_group_name().unwrap();
             let dc_upgrade_type = self.project.get_upgrade_type(&current_tab, &dc);
@@ -755,20 +1556,86 @@ impl App {
                     UpgradeType::Breaking => " + ",
                     _ => "",
                 };
+                let warning_string = match self
+                    .project
+                    .get_current_version(current_tab, &item)
+                    .and_then(|x| {
+                        self.project
+                            .get_withdrawal(current_tab, &item, &x.to_string())
+                    }) {
+                    Some(w) => format!(" !{}", w),
+                    None => "".to_string(),
+                };
+                let vulnerable = !self
+                    .project
+                    .get_vulnerabilities(current_tab, &item)
+                    .is_empty();
+                let warning_string = if vulnerable {
+                    format!("{} !vulnerable", warning_string)
+                } else {
+                    warning_string
+                };
+                let license_status = self.project.get_license_status(current_tab, &item);
+                let unlicensed = license_status == Some(LicenseStatus::Denied);
+                let warning_string = match license_status {
+                    Some(LicenseStatus::Denied) => format!("{} !license", warning_string),
+                    Some(LicenseStatus::Unknown) => format!("{} ?license", warning_string),
+                    _ => warning_string,
+                };
                 let updated_string = match self.updated_items.get(&item) {
                     Some(v) => format!("... updated to {}", v),
                     None => "".to_string(),
                 };
+                // Only packages from the default registry have versions to upgrade to
+                let source = self.project.get_source(current_tab, &item);
+                // Optional dependencies are marked like in `dep?/feature`
+                let optional_string = if self.project.is_optional(current_tab, &item) {
+                    "?"
+                } else {
+                    ""
+                };
+                if let Some(s) = source.filter(|x| x.registry_name().is_none()) {
+                    items.push(Text::styled(
+                        format!(
+                            "{}{} ({}) [{}]",
+                            &item,
+                            optional_string,
+                            stringify(&self.project.get_current_version(current_tab, &item)),
+                            s
+                        ),
+                        Style::default().fg(Color::Blue),
+                    ));
+                    continue;
+                }
+                let mut display_name = match source {
+                    Some(s) => format!("{}{} [{}]", item, optional_string, s.package()),
+                    None => format!("{}{}", item, optional_string),
+                };
+                // Other copies of the package in the lockfile
+                let locked = self
+                    .project
+                    .get_locked_versions(current_tab, &item)
+                    .map_or(0, |x| x.len());
+                if locked > 1 {
+                    display_name = format!("{} *{}", display_name, locked);
+                }
                 items.push(Text::styled(
                     format!(
-                        "{} ({} > {}){}  {}",
-                        &item,
-                        stringify(&self.project.get_current_version(&item)),
+                        "{} ({} > {}){}{}  {}",
+                        &display_name,
+                        stringify(&self.project.get_current_version(current_tab, &item)),
                         stringify(&self.project.get_semver_version(&current_tab, &item)),
                         breaking_changes_string,
+                        warning_string,
                         updated_string
                     ),
-                    Style::default().fg(get_version_color(upgrade_type)),
+                    if vulnerable {
+                        Style::default().fg(Color::Red)
+                    } else if unlicensed {
+                        Style::default().fg(Color::Yellow)
+                    } else {
+                        Style::default().fg(get_version_color(upgrade_type))
+                    },
                 ));
             }
             let mut filters = Vec::new();
@@ -778,6 +1645,12 @@ impl App {
             if !self.show_uptodate {
                 filters.push("updateable");
             }
+            if !self.show_optional {
+                filters.push("default-build");
+            }
+            if self.only_license_violations {
+                filters.push("license");
+            }
             let title = if filters.len() > 0 {
                 format!("Dependencies (filter:{})", filters.join(","))
             } else {
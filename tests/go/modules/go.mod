@@ -0,0 +1,24 @@
+module github.com/meain/depman-example
+
+go 1.16
+
+require (
+	github.com/BurntSushi/toml v0.3.1
+	github.com/go-redis/redis/v8 v8.4.0
+	github.com/gorilla/mux v1.7.4
+	github.com/sirupsen/logrus v1.7.0
+	github.com/spf13/cobra v1.1.1
+	github.com/stretchr/testify v1.6.1
+	golang.org/x/sync v0.0.0-20201020160332-67f06af15bc9
+	gopkg.in/yaml.v2 v2.3.0
+)
+
+require (
+	github.com/davecgh/go-spew v1.1.1 // indirect
+	github.com/pmezard/go-difflib v1.0.0 // indirect
+	golang.org/x/sys v0.0.0-20201119102817-f84b799fce68 // indirect
+)
+
+exclude github.com/sirupsen/logrus v1.7.1
+
+replace github.com/gorilla/mux => ../mux